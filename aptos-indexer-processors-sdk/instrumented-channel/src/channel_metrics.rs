@@ -5,7 +5,10 @@ use prometheus_client::{
     metrics::{counter::Counter, family::Family, gauge::Gauge},
     registry::Registry,
 };
-use std::sync::atomic::AtomicU64;
+use std::{
+    collections::BTreeMap,
+    sync::{atomic::AtomicU64, Mutex},
+};
 
 pub const METRICS_PREFIX: &str = "aptos_procsdk_channel_";
 
@@ -79,6 +82,20 @@ pub static FAILED_RECEIVES_COUNT: Lazy<Family<ChannelMetricLabels, Counter>> =
 pub static CHANNEL_SIZE: Lazy<Family<ChannelMetricLabels, Gauge>> =
     Lazy::new(Family::<ChannelMetricLabels, Gauge>::default);
 
+// Handles to the `CHANNEL_SIZE` gauges, which can't be listed through the family.
+static CHANNEL_SIZES: Lazy<Mutex<BTreeMap<String, Gauge>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Current size of every channel, by the step it is the output of.
+pub fn channel_sizes() -> BTreeMap<String, i64> {
+    CHANNEL_SIZES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(output_of, gauge)| (output_of.clone(), gauge.get()))
+        .collect()
+}
+
 #[derive(Builder, Clone)]
 pub struct ChannelMetrics {
     pub labels: ChannelMetricLabels,
@@ -86,9 +103,13 @@ pub struct ChannelMetrics {
 
 impl ChannelMetrics {
     pub fn new(output_of: String) -> Self {
-        Self {
-            labels: ChannelMetricLabels { output_of },
-        }
+        let labels = ChannelMetricLabels { output_of };
+        CHANNEL_SIZES
+            .lock()
+            .unwrap()
+            .entry(labels.output_of.clone())
+            .or_insert_with(|| CHANNEL_SIZE.get_or_create(&labels).clone());
+        Self { labels }
     }
 }
 
@@ -1,5 +1,9 @@
 use std::{
-    sync::atomic::{AtomicU64, Ordering},
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        RwLock,
+    },
     time::{Duration, SystemTime},
 };
 
@@ -21,8 +25,27 @@ use std::{
 /// // Sampled based on time passed, log at most once a minute
 /// sample!(SampleRate::Duration(Duration::from_secs(60)), info!("Long log"));
 /// ```
+///
+/// `sample_with_policy!` takes a key as well, and runs at the rate set for that key with
+/// `set_sample_rates` instead of the given one, if any. This lets the rates be configured at
+/// runtime.
+///
+/// ```
+/// use sample::{sample_with_policy, set_sample_rates, SampleRate};
+/// use std::time::Duration;
+/// use tracing::info;
+///
+/// set_sample_rates([("batch_received".to_string(), SampleRate::Frequency(100))]);
+///
+/// // Logs every 100th time instead of at most once a second.
+/// sample_with_policy!(
+///     "batch_received",
+///     SampleRate::Duration(Duration::from_secs(1)),
+///     info!("Received batch")
+/// );
+/// ```
 /// The rate at which a `sample!` macro will run it's given function
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SampleRate {
     /// Only sample a single time during a window of time. This rate only has a resolution in
     /// seconds.
@@ -35,24 +58,49 @@ pub enum SampleRate {
     Always,
 }
 
+static SAMPLE_RATES: RwLock<BTreeMap<String, SampleRate>> = RwLock::new(BTreeMap::new());
+
+/// Sets the sampling policy: the rates of the `sample_with_policy!` call sites, by key. Keys
+/// that aren't set use the rate given at the call site.
+pub fn set_sample_rates(rates: impl IntoIterator<Item = (String, SampleRate)>) {
+    *SAMPLE_RATES.write().unwrap() = rates.into_iter().collect();
+}
+
 /// An internal struct that can be checked if a sample is ready for the `sample!` macro
 pub struct Sampling {
     rate: SampleRate,
-    state: AtomicU64,
+    // Events left until the next sample, for `SampleRate::Frequency`.
+    count: AtomicU64,
+    // Seconds since the Unix Epoch of the last sample, for `SampleRate::Duration`.
+    last_sample: AtomicU64,
 }
 
 impl Sampling {
     pub const fn new(rate: SampleRate) -> Self {
         Self {
             rate,
-            state: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+            last_sample: AtomicU64::new(0),
         }
     }
 
     pub fn sample(&self) -> bool {
-        match &self.rate {
-            SampleRate::Duration(rate) => Self::sample_duration(rate, &self.state),
-            SampleRate::Frequency(rate) => Self::sample_frequency(*rate, &self.state),
+        self.sample_at(&self.rate)
+    }
+
+    /// Samples at the rate set for `key` with `set_sample_rates`, or at this sampling's rate
+    /// if there is none.
+    pub fn sample_with_policy(&self, key: &str) -> bool {
+        match SAMPLE_RATES.read().unwrap().get(key) {
+            Some(rate) => self.sample_at(rate),
+            None => self.sample(),
+        }
+    }
+
+    fn sample_at(&self, rate: &SampleRate) -> bool {
+        match rate {
+            SampleRate::Duration(rate) => Self::sample_duration(rate, &self.last_sample),
+            SampleRate::Frequency(rate) => Self::sample_frequency(*rate, &self.count),
             SampleRate::Always => true,
         }
     }
@@ -108,6 +156,21 @@ macro_rules! sample {
     }};
 }
 
+/// Like `sample!`, but at the rate set for `$key` with `set_sample_rates`, if any.
+#[macro_export]
+macro_rules! sample_with_policy {
+    ($key:expr, $sample_rate:expr, $($args:expr)+ ,) => {
+        $crate::sample_with_policy!($key, $sample_rate, $($args)+);
+    };
+
+    ($key:expr, $sample_rate:expr, $($args:tt)+) => {{
+        static SAMPLING: $crate::Sampling = $crate::Sampling::new($sample_rate);
+        if SAMPLING.sample_with_policy($key) {
+            $($args)+
+        }
+    }};
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,6 +220,26 @@ mod tests {
         assert_eq!(v.len(), 2);
     }
 
+    #[test]
+    fn policy() {
+        let sampling = Sampling::new(SampleRate::Always);
+        set_sample_rates([("policy_test".to_string(), SampleRate::Frequency(10))]);
+        let v: Vec<_> = (0..=25)
+            .filter(|_| sampling.sample_with_policy("policy_test"))
+            .collect();
+        assert_eq!(v, vec![0, 10, 20]);
+
+        // Keys without a rate use the call site's rate.
+        assert!((0..5).all(|_| sampling.sample_with_policy("other_key")));
+
+        let mut count = 0;
+        for _ in 0..10 {
+            sample_with_policy!("policy_test", SampleRate::Always, count += 1);
+        }
+        assert_eq!(count, 1);
+        set_sample_rates([]);
+    }
+
     #[test]
     fn macro_expansion() {
         for i in 0..10 {
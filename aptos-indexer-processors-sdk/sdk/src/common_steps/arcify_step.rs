@@ -71,6 +71,7 @@ mod tests {
                 start_transaction_timestamp: None,
                 end_transaction_timestamp: None,
                 total_size_in_bytes: 0,
+                span: Default::default(),
             },
         }
     }
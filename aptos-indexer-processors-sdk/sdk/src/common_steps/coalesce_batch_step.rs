@@ -0,0 +1,256 @@
+use crate::{
+    common_steps::Sizeable,
+    traits::{
+        pollable_async_step::PollableAsyncRunType, NamedStep, PollableAsyncStep, Processable,
+    },
+    types::transaction_context::TransactionContext,
+    utils::errors::ProcessorError,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Targets for CoalesceBatchStep. A merged batch is emitted once it reaches any of the
+/// targets that are set, or once adding the next batch would go over one.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CoalesceBatchConfig {
+    #[serde(default)]
+    pub target_transactions: Option<u64>,
+    #[serde(default)]
+    pub target_items: Option<usize>,
+    /// Size of the items, as measured by `Sizeable`.
+    #[serde(default)]
+    pub target_bytes: Option<u64>,
+    /// A partial batch is emitted once it has waited this long, so that slow periods don't
+    /// hold data back.
+    #[serde(default = "CoalesceBatchConfig::default_max_wait_ms")]
+    pub max_wait_ms: u64,
+}
+
+impl CoalesceBatchConfig {
+    pub const fn default_max_wait_ms() -> u64 {
+        1000
+    }
+}
+
+struct PendingBatch<T> {
+    context: TransactionContext<Vec<T>>,
+    num_bytes: u64,
+    since: Instant,
+}
+
+/// Merges adjacent batches into batches of a steady size.
+///
+/// Only contiguous batches are merged; a batch that doesn't directly follow the pending one
+/// flushes it. Merged batches cover exactly the versions of their inputs, so a downstream
+/// `VersionTrackerStep` sees the same contiguous ranges.
+pub struct CoalesceBatchStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Sizeable + Send + 'static,
+{
+    config: CoalesceBatchConfig,
+    pending: Option<PendingBatch<T>>,
+}
+
+impl<T> CoalesceBatchStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Sizeable + Send + 'static,
+{
+    pub fn new(config: CoalesceBatchConfig) -> Self {
+        Self {
+            config,
+            pending: None,
+        }
+    }
+
+    fn exceeds_target(&self, num_transactions: u64, num_items: usize, num_bytes: u64) -> bool {
+        self.config
+            .target_transactions
+            .is_some_and(|target| num_transactions > target)
+            || self
+                .config
+                .target_items
+                .is_some_and(|target| num_items > target)
+            || self
+                .config
+                .target_bytes
+                .is_some_and(|target| num_bytes > target)
+    }
+
+    fn reached_target(&self, pending: &PendingBatch<T>) -> bool {
+        self.config
+            .target_transactions
+            .is_some_and(|target| pending.context.get_num_transactions() >= target)
+            || self
+                .config
+                .target_items
+                .is_some_and(|target| pending.context.data.len() >= target)
+            || self
+                .config
+                .target_bytes
+                .is_some_and(|target| pending.num_bytes >= target)
+    }
+}
+
+#[async_trait]
+impl<T> Processable for CoalesceBatchStep<T>
+where
+    T: Sizeable + Send + Sync + 'static,
+{
+    type Input = Vec<T>;
+    type Output = Vec<T>;
+    type RunType = PollableAsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<T>>,
+    ) -> Result<Option<TransactionContext<Vec<T>>>, ProcessorError> {
+        let num_bytes = item.data.size_in_bytes();
+        let mut output = None;
+        match self.pending.take() {
+            Some(mut pending)
+                if pending.context.metadata.end_version + 1 == item.metadata.start_version
+                    && !self.exceeds_target(
+                        item.metadata.end_version - pending.context.metadata.start_version + 1,
+                        pending.context.data.len() + item.data.len(),
+                        pending.num_bytes + num_bytes,
+                    ) =>
+            {
+                pending.context.metadata.extend(&item.metadata);
+                pending.context.data.extend(item.data);
+                pending.num_bytes += num_bytes;
+                self.pending = Some(pending);
+            },
+            pending => {
+                output = pending.map(|pending| pending.context);
+                self.pending = Some(PendingBatch {
+                    context: item,
+                    num_bytes,
+                    since: Instant::now(),
+                });
+            },
+        }
+        // Only one batch can be returned at a time. If one was just flushed, the new pending
+        // batch goes out with the next batch or poll.
+        if output.is_none()
+            && self
+                .pending
+                .as_ref()
+                .is_some_and(|pending| self.reached_target(pending))
+        {
+            output = self.pending.take().map(|pending| pending.context);
+        }
+        Ok(output)
+    }
+
+    async fn cleanup(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        Ok(self.pending.take().map(|pending| vec![pending.context]))
+    }
+}
+
+#[async_trait]
+impl<T> PollableAsyncStep for CoalesceBatchStep<T>
+where
+    T: Sizeable + Send + Sync + 'static,
+{
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.config.max_wait_ms)
+    }
+
+    async fn poll(&mut self) -> Result<Option<Vec<TransactionContext<Vec<T>>>>, ProcessorError> {
+        let max_wait = Duration::from_millis(self.config.max_wait_ms);
+        let should_flush = self.pending.as_ref().is_some_and(|pending| {
+            pending.since.elapsed() >= max_wait || self.reached_target(pending)
+        });
+        if !should_flush {
+            return Ok(None);
+        }
+        Ok(self.pending.take().map(|pending| vec![pending.context]))
+    }
+}
+
+impl<T> NamedStep for CoalesceBatchStep<T>
+where
+    T: Sizeable + Send + 'static,
+{
+    fn name(&self) -> String {
+        format!("CoalesceBatchStep: {}", std::any::type_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction_context::TransactionMetadata;
+    use aptos_protos::util::timestamp::Timestamp;
+
+    struct TestRow;
+
+    impl Sizeable for TestRow {
+        fn size_in_bytes(&self) -> u64 {
+            10
+        }
+    }
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<Vec<TestRow>> {
+        TransactionContext {
+            data: (start_version..=end_version).map(|_| TestRow).collect(),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                start_transaction_timestamp: Some(Timestamp {
+                    seconds: start_version as i64,
+                    nanos: 0,
+                }),
+                end_transaction_timestamp: Some(Timestamp {
+                    seconds: end_version as i64,
+                    nanos: 0,
+                }),
+                total_size_in_bytes: 1,
+                span: Default::default(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_coalesce_batches() {
+        let mut step = CoalesceBatchStep::new(CoalesceBatchConfig {
+            target_transactions: Some(10),
+            target_items: None,
+            target_bytes: None,
+            max_wait_ms: CoalesceBatchConfig::default_max_wait_ms(),
+        });
+        assert!(step.process(batch(0, 3)).await.unwrap().is_none());
+        assert!(step.process(batch(4, 7)).await.unwrap().is_none());
+        // Adding this batch would go over the target, so the pending one is emitted.
+        let output = step.process(batch(8, 11)).await.unwrap().unwrap();
+        assert_eq!(output.metadata.start_version, 0);
+        assert_eq!(output.metadata.end_version, 7);
+        assert_eq!(
+            output.metadata.start_transaction_timestamp.unwrap().seconds,
+            0
+        );
+        assert_eq!(
+            output.metadata.end_transaction_timestamp.unwrap().seconds,
+            7
+        );
+        assert_eq!(output.metadata.total_size_in_bytes, 2);
+        assert_eq!(output.data.len(), 8);
+
+        // Non-contiguous batches aren't merged.
+        let output = step.process(batch(20, 21)).await.unwrap().unwrap();
+        assert_eq!(output.metadata.start_version, 8);
+        assert_eq!(output.metadata.end_version, 11);
+
+        let remaining = step.cleanup().await.unwrap().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].metadata.start_version, 20);
+    }
+}
@@ -0,0 +1,493 @@
+use crate::{
+    traits::{AsyncRunType, AsyncStep, NamedStep, Processable},
+    types::transaction_context::TransactionContext,
+    utils::errors::ProcessorError,
+};
+use ahash::AHashSet;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    hash::Hash,
+    io::{BufRead, BufReader, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// Durable record of the keys a `DedupStep` has emitted, so that duplicates are still
+/// dropped after a restart. Every key is recorded with the end version of its batch.
+#[async_trait]
+pub trait SeenSetStore<K>: Send + Sync {
+    /// Returns the keys recorded at versions `>= min_version`. Older keys can be forgotten.
+    async fn load(&self, min_version: u64) -> Result<Vec<(u64, K)>>;
+
+    /// Durably records `keys`. `min_version` is the oldest version the step still remembers,
+    /// so keys recorded before it can be dropped.
+    async fn append(&self, keys: &[(u64, K)], min_version: u64) -> Result<()>;
+}
+
+/// Keys emitted by a `DedupStep` that downstream hasn't confirmed yet, by batch end version.
+struct PendingKeys<K> {
+    batches: BTreeMap<u64, Vec<(u64, K)>>,
+    // Oldest version still remembered by the `DedupStep`.
+    min_version: u64,
+}
+
+/// Drops items whose key was already emitted.
+///
+/// Keys are remembered in a window of at most `max_keys` keys, oldest first. With a
+/// `SeenSetStore`, the keys are persisted by the step returned from `commit_step`, placed
+/// after the sink, and reloaded on startup. So batches replayed after a restart from the
+/// checkpoint are deduplicated too, while batches that never reached the sink are emitted
+/// again.
+pub struct DedupStep<T, K>
+where
+    Self: Sized + Send + 'static,
+    T: Send + 'static,
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    key_fn: Box<dyn Fn(&T) -> K + Send + Sync>,
+    max_keys: usize,
+    window: VecDeque<(u64, K)>,
+    seen: AHashSet<K>,
+    store: Option<Arc<dyn SeenSetStore<K>>>,
+    pending: Arc<Mutex<PendingKeys<K>>>,
+}
+
+impl<T, K> DedupStep<T, K>
+where
+    Self: Sized + Send + 'static,
+    T: Send + 'static,
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    pub fn new<F>(max_keys: usize, key_fn: F) -> Self
+    where
+        F: Fn(&T) -> K + Send + Sync + 'static,
+    {
+        Self {
+            key_fn: Box::new(key_fn),
+            max_keys,
+            window: VecDeque::new(),
+            seen: AHashSet::new(),
+            store: None,
+            pending: Arc::new(Mutex::new(PendingKeys {
+                batches: BTreeMap::new(),
+                min_version: 0,
+            })),
+        }
+    }
+
+    /// Persists the seen keys in `store` and loads the keys recorded at or after
+    /// `checkpoint_version`, the version the processor resumes from. Older batches are never
+    /// replayed, so their keys aren't needed.
+    ///
+    /// Keys are only persisted by the step returned from `commit_step`. Without it, nothing
+    /// is persisted and the keys of emitted batches pile up in memory.
+    pub async fn with_seen_set_store(
+        mut self,
+        store: impl SeenSetStore<K> + 'static,
+        checkpoint_version: u64,
+    ) -> Result<Self, ProcessorError> {
+        let keys =
+            store
+                .load(checkpoint_version)
+                .await
+                .map_err(|e| ProcessorError::StepInitError {
+                    message: format!("Failed to load seen keys: {e:?}"),
+                })?;
+        for (version, key) in keys {
+            self.remember(version, key);
+        }
+        self.store = Some(Arc::new(store));
+        Ok(self)
+    }
+
+    /// Returns the step that persists the keys of the batches passing through it. Place it
+    /// after the sink, so that only keys of delivered batches are reloaded after a restart.
+    /// A batch confirms the keys of every deduplicated batch whose end version it covers.
+    pub fn commit_step<O>(&self) -> DedupCommitStep<O, K>
+    where
+        O: Send + Sync + 'static,
+    {
+        DedupCommitStep {
+            store: self.store.clone(),
+            pending: self.pending.clone(),
+            _output: PhantomData,
+        }
+    }
+
+    fn remember(&mut self, version: u64, key: K) {
+        if !self.seen.insert(key.clone()) {
+            return;
+        }
+        self.window.push_back((version, key));
+        while self.window.len() > self.max_keys {
+            if let Some((_, key)) = self.window.pop_front() {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<T, K> Processable for DedupStep<T, K>
+where
+    T: Send + Sync + 'static,
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    type Input = Vec<T>;
+    type Output = Vec<T>;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        mut item: TransactionContext<Vec<T>>,
+    ) -> Result<Option<TransactionContext<Vec<T>>>, ProcessorError> {
+        let version = item.metadata.end_version;
+        let num_items = item.data.len();
+        let mut batch_keys = AHashSet::new();
+        let mut new_keys = Vec::new();
+        item.data.retain(|row| {
+            let key = (self.key_fn)(row);
+            if self.seen.contains(&key) || !batch_keys.insert(key.clone()) {
+                return false;
+            }
+            new_keys.push((version, key));
+            true
+        });
+        if num_items > item.data.len() {
+            debug!(
+                start_version = item.metadata.start_version,
+                end_version = item.metadata.end_version,
+                dropped = num_items - item.data.len(),
+                "Dropped duplicate items"
+            );
+        }
+
+        for (version, key) in new_keys.iter().cloned() {
+            self.remember(version, key);
+        }
+        if self.store.is_some() && !new_keys.is_empty() {
+            let mut pending = self.pending.lock().await;
+            pending.min_version = self.window.front().map(|(v, _)| *v).unwrap_or(version);
+            pending.batches.entry(version).or_default().extend(new_keys);
+        }
+        Ok(Some(item))
+    }
+}
+
+impl<T, K> AsyncStep for DedupStep<T, K>
+where
+    T: Send + Sync + 'static,
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+{
+}
+
+impl<T, K> NamedStep for DedupStep<T, K>
+where
+    T: Send + 'static,
+    K: Eq + Hash + Clone + Send + Sync + 'static,
+{
+    fn name(&self) -> String {
+        format!(
+            "DedupStep: {} by {}",
+            std::any::type_name::<T>(),
+            std::any::type_name::<K>()
+        )
+    }
+}
+
+/// Persists the keys a `DedupStep` emitted once their batches reach this step. See
+/// `DedupStep::commit_step`.
+pub struct DedupCommitStep<O, K>
+where
+    Self: Sized + Send + 'static,
+    O: Send + 'static,
+    K: Send + Sync + 'static,
+{
+    store: Option<Arc<dyn SeenSetStore<K>>>,
+    pending: Arc<Mutex<PendingKeys<K>>>,
+    _output: PhantomData<fn() -> O>,
+}
+
+#[async_trait]
+impl<O, K> Processable for DedupCommitStep<O, K>
+where
+    O: Send + Sync + 'static,
+    K: Send + Sync + 'static,
+{
+    type Input = O;
+    type Output = O;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<O>,
+    ) -> Result<Option<TransactionContext<O>>, ProcessorError> {
+        let Some(store) = &self.store else {
+            return Ok(Some(item));
+        };
+        let mut pending = self.pending.lock().await;
+        let confirmed: Vec<u64> = pending
+            .batches
+            .range(item.metadata.start_version..=item.metadata.end_version)
+            .map(|(version, _)| *version)
+            .collect();
+        let keys: Vec<(u64, K)> = confirmed
+            .iter()
+            .filter_map(|version| pending.batches.remove(version))
+            .flatten()
+            .collect();
+        if !keys.is_empty() {
+            store
+                .append(&keys, pending.min_version)
+                .await
+                .map_err(|e| ProcessorError::ProcessError {
+                    message: format!("Failed to persist seen keys: {e:?}"),
+                })?;
+        }
+        Ok(Some(item))
+    }
+}
+
+impl<O, K> AsyncStep for DedupCommitStep<O, K>
+where
+    O: Send + Sync + 'static,
+    K: Send + Sync + 'static,
+{
+}
+
+impl<O, K> NamedStep for DedupCommitStep<O, K>
+where
+    O: Send + 'static,
+    K: Send + Sync + 'static,
+{
+    fn name(&self) -> String {
+        format!(
+            "DedupCommitStep: {} by {}",
+            std::any::type_name::<O>(),
+            std::any::type_name::<K>()
+        )
+    }
+}
+
+/// `SeenSetStore` that appends keys to a file of JSON lines. Once the file has more than
+/// `max_entries` lines, it's compacted to the keys at or after the oldest remembered version.
+#[derive(Clone)]
+pub struct FileSeenSetStore {
+    path: PathBuf,
+    max_entries: usize,
+    // Number of lines in the file.
+    num_entries: Arc<Mutex<usize>>,
+}
+
+impl FileSeenSetStore {
+    pub fn new(path: impl Into<PathBuf>, max_entries: usize) -> Self {
+        Self {
+            path: path.into(),
+            max_entries,
+            num_entries: Arc::new(Mutex::new(0)),
+        }
+    }
+}
+
+#[async_trait]
+impl<K> SeenSetStore<K> for FileSeenSetStore
+where
+    K: Serialize + DeserializeOwned + Send + Sync + 'static,
+{
+    async fn load(&self, min_version: u64) -> Result<Vec<(u64, K)>> {
+        let mut num_entries = self.num_entries.lock().await;
+        let path = self.path.clone();
+        let keys = tokio::task::spawn_blocking(move || compact::<K>(&path, min_version))
+            .await
+            .context("Task panicked loading seen keys")??;
+        *num_entries = keys.len();
+        Ok(keys)
+    }
+
+    async fn append(&self, keys: &[(u64, K)], min_version: u64) -> Result<()> {
+        let mut lines = Vec::new();
+        for key in keys {
+            serde_json::to_writer(&mut lines, key)?;
+            lines.push(b'\n');
+        }
+        let mut num_entries = self.num_entries.lock().await;
+        let path = self.path.clone();
+        let needs_compaction = *num_entries + keys.len() > self.max_entries;
+        let compacted = tokio::task::spawn_blocking(move || -> Result<Option<usize>> {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .with_context(|| format!("Failed to open seen keys file at {path:?}"))?;
+            file.write_all(&lines)?;
+            file.sync_data()?;
+            if !needs_compaction {
+                return Ok(None);
+            }
+            Ok(Some(compact::<K>(&path, min_version)?.len()))
+        })
+        .await
+        .context("Task panicked persisting seen keys")??;
+        *num_entries = compacted.unwrap_or(*num_entries + keys.len());
+        Ok(())
+    }
+}
+
+/// Rewrites the file with only the keys at or after `min_version`, and returns them.
+fn compact<K: Serialize + DeserializeOwned>(
+    path: &Path,
+    min_version: u64,
+) -> Result<Vec<(u64, K)>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e).with_context(|| format!("Failed to open {path:?}")),
+    };
+    let mut keys = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        // A crash can leave a partially written last line, whose batch wasn't emitted.
+        let Ok((version, key)) = serde_json::from_str::<(u64, K)>(&line) else {
+            continue;
+        };
+        if version >= min_version {
+            keys.push((version, key));
+        }
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    let mut file = File::create(&tmp_path)?;
+    for key in &keys {
+        serde_json::to_writer(&mut file, key)?;
+        file.write_all(b"\n")?;
+    }
+    file.sync_all()?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to move {tmp_path:?}"))?;
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction_context::TransactionMetadata;
+
+    // (transaction_version, event_index)
+    type Event = (u64, u64);
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<Vec<Event>> {
+        TransactionContext {
+            data: (start_version..=end_version)
+                .flat_map(|version| [(version, 0), (version, 1)])
+                .collect(),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_dedup_window() {
+        let mut step = DedupStep::new(4, |event: &Event| *event);
+        assert_eq!(
+            step.process(batch(0, 1)).await.unwrap().unwrap().data.len(),
+            4
+        );
+        // A replayed batch is dropped entirely.
+        assert!(step
+            .process(batch(0, 1))
+            .await
+            .unwrap()
+            .unwrap()
+            .data
+            .is_empty());
+        assert_eq!(
+            step.process(batch(2, 3)).await.unwrap().unwrap().data.len(),
+            4
+        );
+        // Keys that fell out of the window aren't deduplicated anymore.
+        assert_eq!(
+            step.process(batch(0, 0)).await.unwrap().unwrap().data.len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_dedup_across_restarts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen.jsonl");
+
+        let mut step = DedupStep::new(100, |event: &Event| *event)
+            .with_seen_set_store(FileSeenSetStore::new(&path, 100), 0)
+            .await
+            .unwrap();
+        let mut commit_step = step.commit_step::<Vec<Event>>();
+        for batch in [batch(0, 4), batch(5, 9)] {
+            let output = step.process(batch).await.unwrap().unwrap();
+            commit_step.process(output).await.unwrap();
+        }
+
+        // Restart from a checkpoint at version 5. Only the keys that can be replayed are
+        // reloaded.
+        let mut step = DedupStep::new(100, |event: &Event| *event)
+            .with_seen_set_store(FileSeenSetStore::new(&path, 100), 5)
+            .await
+            .unwrap();
+        assert_eq!(step.window.len(), 10);
+        let output = step.process(batch(5, 12)).await.unwrap().unwrap();
+        assert_eq!(output.data, vec![
+            (10, 0),
+            (10, 1),
+            (11, 0),
+            (11, 1),
+            (12, 0),
+            (12, 1)
+        ]);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_undelivered_batches_are_emitted_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seen.jsonl");
+
+        let mut step = DedupStep::new(100, |event: &Event| *event)
+            .with_seen_set_store(FileSeenSetStore::new(&path, 100), 0)
+            .await
+            .unwrap();
+        let mut commit_step = step.commit_step::<Vec<Event>>();
+        let output = step.process(batch(0, 4)).await.unwrap().unwrap();
+        commit_step.process(output).await.unwrap();
+        // Crash after deduplicating the next batch, but before the sink wrote it.
+        step.process(batch(5, 9)).await.unwrap();
+
+        let mut step = DedupStep::new(100, |event: &Event| *event)
+            .with_seen_set_store(FileSeenSetStore::new(&path, 100), 0)
+            .await
+            .unwrap();
+        assert!(step
+            .process(batch(0, 4))
+            .await
+            .unwrap()
+            .unwrap()
+            .data
+            .is_empty());
+        // The batch that never reached the sink is emitted again.
+        assert_eq!(
+            step.process(batch(5, 9)).await.unwrap().unwrap().data.len(),
+            10
+        );
+    }
+}
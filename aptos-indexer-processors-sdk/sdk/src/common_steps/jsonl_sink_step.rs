@@ -0,0 +1,388 @@
+use crate::{
+    aptos_indexer_transaction_stream::utils::time::timestamp_to_iso,
+    traits::{AsyncRunType, AsyncStep, NamedStep, Processable},
+    types::transaction_context::{TransactionContext, TransactionMetadata},
+    utils::errors::ProcessorError,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Stdout, Write},
+    path::PathBuf,
+};
+use tracing::info;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JsonlCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl JsonlCompression {
+    fn file_extension(&self) -> &'static str {
+        match self {
+            JsonlCompression::None => "jsonl",
+            JsonlCompression::Gzip => "jsonl.gz",
+            JsonlCompression::Zstd => "jsonl.zst",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct JsonlSinkConfig {
+    /// Directory to write the files to. If not set, lines are written to stdout.
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    /// Files are named `{file_prefix}_{start_version}.jsonl`, plus the compression extension.
+    #[serde(default = "JsonlSinkConfig::default_file_prefix")]
+    pub file_prefix: String,
+    /// A new file is started once the current one has this many uncompressed bytes.
+    #[serde(default = "JsonlSinkConfig::default_max_file_size_bytes")]
+    pub max_file_size_bytes: u64,
+    /// If true, every line is `{"metadata": ..., "item": ...}` with the batch's metadata.
+    #[serde(default)]
+    pub include_metadata: bool,
+    /// Ignored when writing to stdout.
+    #[serde(default)]
+    pub compression: JsonlCompression,
+}
+
+impl JsonlSinkConfig {
+    pub fn default_file_prefix() -> String {
+        "output".to_string()
+    }
+
+    pub const fn default_max_file_size_bytes() -> u64 {
+        100 * 1024 * 1024
+    }
+}
+
+/// Serializable view of `TransactionMetadata`.
+#[derive(Serialize)]
+struct JsonlMetadata {
+    start_version: u64,
+    end_version: u64,
+    start_transaction_timestamp: Option<String>,
+    end_transaction_timestamp: Option<String>,
+}
+
+impl From<&TransactionMetadata> for JsonlMetadata {
+    fn from(metadata: &TransactionMetadata) -> Self {
+        Self {
+            start_version: metadata.start_version,
+            end_version: metadata.end_version,
+            start_transaction_timestamp: metadata
+                .start_transaction_timestamp
+                .as_ref()
+                .map(timestamp_to_iso),
+            end_transaction_timestamp: metadata
+                .end_transaction_timestamp
+                .as_ref()
+                .map(timestamp_to_iso),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonlLine<'a, T> {
+    metadata: &'a JsonlMetadata,
+    item: &'a T,
+}
+
+enum JsonlWriter {
+    Stdout(Stdout),
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl JsonlWriter {
+    fn create(path: &PathBuf, compression: JsonlCompression) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(match compression {
+            JsonlCompression::None => JsonlWriter::Plain(file),
+            JsonlCompression::Gzip => {
+                JsonlWriter::Gzip(GzEncoder::new(file, Compression::default()))
+            },
+            JsonlCompression::Zstd => JsonlWriter::Zstd(zstd::Encoder::new(file, 0)?),
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            JsonlWriter::Stdout(stdout) => stdout,
+            JsonlWriter::Plain(file) => file,
+            JsonlWriter::Gzip(encoder) => encoder,
+            JsonlWriter::Zstd(encoder) => encoder,
+        }
+    }
+
+    fn file(&self) -> Option<&File> {
+        match self {
+            JsonlWriter::Stdout(_) => None,
+            JsonlWriter::Plain(file) => Some(file.get_ref()),
+            JsonlWriter::Gzip(encoder) => Some(encoder.get_ref().get_ref()),
+            JsonlWriter::Zstd(encoder) => Some(encoder.get_ref().get_ref()),
+        }
+    }
+
+    /// Writes `lines` and makes them durable, so that they survive a crash once this returns.
+    fn write_and_flush(&mut self, lines: &[u8]) -> io::Result<()> {
+        let writer = self.writer();
+        writer.write_all(lines)?;
+        writer.flush()?;
+        if let Some(file) = self.file() {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Ends the compressed stream, if any, and syncs the file.
+    fn finish(self) -> io::Result<()> {
+        let file = match self {
+            JsonlWriter::Stdout(mut stdout) => return stdout.flush(),
+            JsonlWriter::Plain(file) => file,
+            JsonlWriter::Gzip(encoder) => encoder.finish()?,
+            JsonlWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+}
+
+/// Writes every item as a line of JSON, to rotating files or to stdout.
+///
+/// A batch is only passed downstream once its lines are flushed (and synced, for files), so
+/// a downstream `VersionTrackerStep` never checkpoints versions that aren't written yet.
+pub struct JsonlSinkStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    config: JsonlSinkConfig,
+    // Taken while a write is in progress on the blocking thread pool.
+    writer: Option<JsonlWriter>,
+    current_file_size: u64,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T> JsonlSinkStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    pub fn new(config: JsonlSinkConfig) -> Result<Self, ProcessorError> {
+        if let Some(output_dir) = &config.output_dir {
+            std::fs::create_dir_all(output_dir).map_err(|e| ProcessorError::StepInitError {
+                message: format!("Failed to create output directory {output_dir:?}: {e:?}"),
+            })?;
+        }
+        Ok(Self {
+            config,
+            writer: None,
+            current_file_size: 0,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    fn serialize_lines(
+        &self,
+        item: &TransactionContext<Vec<T>>,
+    ) -> Result<Vec<u8>, ProcessorError> {
+        let metadata = JsonlMetadata::from(&item.metadata);
+        let mut lines = Vec::new();
+        for row in &item.data {
+            let result = if self.config.include_metadata {
+                serde_json::to_writer(&mut lines, &JsonlLine {
+                    metadata: &metadata,
+                    item: row,
+                })
+            } else {
+                serde_json::to_writer(&mut lines, row)
+            };
+            result.map_err(|e| ProcessorError::ProcessError {
+                message: format!("Failed to serialize item: {e:?}"),
+            })?;
+            lines.push(b'\n');
+        }
+        Ok(lines)
+    }
+
+    fn take_writer(&mut self, start_version: u64) -> Result<JsonlWriter, ProcessorError> {
+        if let Some(writer) = self.writer.take() {
+            return Ok(writer);
+        }
+        let Some(output_dir) = &self.config.output_dir else {
+            return Ok(JsonlWriter::Stdout(io::stdout()));
+        };
+        let path = output_dir.join(format!(
+            "{}_{start_version}.{}",
+            self.config.file_prefix,
+            self.config.compression.file_extension()
+        ));
+        info!(path = ?path, "Opening new JSONL file");
+        self.current_file_size = 0;
+        JsonlWriter::create(&path, self.config.compression).map_err(|e| {
+            ProcessorError::ProcessError {
+                message: format!("Failed to create file {path:?}: {e:?}"),
+            }
+        })
+    }
+
+    async fn finish_writer(&mut self) -> Result<(), ProcessorError> {
+        if let Some(writer) = self.writer.take() {
+            tokio::task::spawn_blocking(move || writer.finish())
+                .await
+                .expect("Task panicked closing JSONL file")
+                .map_err(|e| ProcessorError::ProcessError {
+                    message: format!("Failed to close JSONL file: {e:?}"),
+                })?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T> Processable for JsonlSinkStep<T>
+where
+    T: Serialize + Send + Sync + 'static,
+{
+    type Input = Vec<T>;
+    type Output = Vec<T>;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<T>>,
+    ) -> Result<Option<TransactionContext<Vec<T>>>, ProcessorError> {
+        let lines = self.serialize_lines(&item)?;
+        if !lines.is_empty() {
+            let mut writer = self.take_writer(item.metadata.start_version)?;
+            let num_bytes = lines.len() as u64;
+            let writer =
+                tokio::task::spawn_blocking(move || writer.write_and_flush(&lines).map(|_| writer))
+                    .await
+                    .expect("Task panicked writing JSONL")
+                    .map_err(|e| ProcessorError::ProcessError {
+                        message: format!("Failed to write JSONL: {e:?}"),
+                    })?;
+            self.writer = Some(writer);
+            self.current_file_size += num_bytes;
+            if self.config.output_dir.is_some()
+                && self.current_file_size >= self.config.max_file_size_bytes
+            {
+                self.finish_writer().await?;
+            }
+        }
+        Ok(Some(item))
+    }
+
+    async fn cleanup(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        self.finish_writer().await?;
+        Ok(None)
+    }
+}
+
+impl<T> AsyncStep for JsonlSinkStep<T> where T: Serialize + Send + Sync + 'static {}
+
+impl<T> NamedStep for JsonlSinkStep<T>
+where
+    T: Serialize + Send + 'static,
+{
+    fn name(&self) -> String {
+        format!("JsonlSinkStep: {}", std::any::type_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[derive(Serialize)]
+    struct TestRow {
+        version: u64,
+    }
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<Vec<TestRow>> {
+        TransactionContext {
+            data: (start_version..=end_version)
+                .map(|version| TestRow { version })
+                .collect(),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    fn read_lines(path: PathBuf, compression: JsonlCompression) -> Vec<serde_json::Value> {
+        let file = File::open(path).unwrap();
+        let mut contents = String::new();
+        match compression {
+            JsonlCompression::None => {
+                let mut file = file;
+                file.read_to_string(&mut contents).unwrap();
+            },
+            JsonlCompression::Gzip => {
+                flate2::read::MultiGzDecoder::new(file)
+                    .read_to_string(&mut contents)
+                    .unwrap();
+            },
+            JsonlCompression::Zstd => {
+                zstd::Decoder::new(file)
+                    .unwrap()
+                    .read_to_string(&mut contents)
+                    .unwrap();
+            },
+        }
+        contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_jsonl_sink_rotates_files() {
+        for compression in [
+            JsonlCompression::None,
+            JsonlCompression::Gzip,
+            JsonlCompression::Zstd,
+        ] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut step = JsonlSinkStep::<TestRow>::new(JsonlSinkConfig {
+                output_dir: Some(dir.path().to_path_buf()),
+                file_prefix: "test".to_string(),
+                // Every batch is bigger than this, so each one gets its own file.
+                max_file_size_bytes: 1,
+                include_metadata: true,
+                compression,
+            })
+            .unwrap();
+
+            let output = step.process(batch(0, 2)).await.unwrap().unwrap();
+            assert_eq!(output.data.len(), 3);
+            step.process(batch(3, 4)).await.unwrap().unwrap();
+            step.cleanup().await.unwrap();
+
+            let extension = compression.file_extension();
+            let lines = read_lines(dir.path().join(format!("test_0.{extension}")), compression);
+            assert_eq!(lines.len(), 3);
+            assert_eq!(lines[0]["item"]["version"], 0);
+            assert_eq!(lines[0]["metadata"]["end_version"], 2);
+            let lines = read_lines(dir.path().join(format!("test_3.{extension}")), compression);
+            assert_eq!(lines.len(), 2);
+            assert_eq!(lines[1]["item"]["version"], 4);
+        }
+    }
+}
@@ -1,16 +1,39 @@
 pub mod arcify_step;
+pub mod coalesce_batch_step;
+pub mod dedup_step;
+#[cfg(feature = "jsonl_sink")]
+pub mod jsonl_sink_step;
 pub mod order_by_version_step;
+#[cfg(feature = "parquet_sink")]
+pub mod parquet_sink_step;
+pub mod shard_filter_step;
+pub mod split_batch_step;
 pub mod timed_buffer_step;
 pub mod transaction_stream_step;
 pub mod version_tracker_step;
+#[cfg(feature = "webhook_sink")]
+pub mod webhook_sink_step;
 pub mod write_rate_limit_step;
 
 // Re-export the steps
 pub use arcify_step::ArcifyStep;
-pub use order_by_version_step::OrderByVersionStep;
-pub use timed_buffer_step::TimedBufferStep;
+pub use coalesce_batch_step::{CoalesceBatchConfig, CoalesceBatchStep};
+pub use dedup_step::{DedupCommitStep, DedupStep, FileSeenSetStore, SeenSetStore};
+#[cfg(feature = "jsonl_sink")]
+pub use jsonl_sink_step::{JsonlCompression, JsonlSinkConfig, JsonlSinkStep};
+pub use order_by_version_step::{OrderByVersionConfig, OrderByVersionStep};
+#[cfg(feature = "parquet_sink")]
+pub use parquet_sink_step::{ParquetSchema, ParquetSinkConfig, ParquetSinkStep};
+pub use shard_filter_step::{ShardFilterStep, ShardingConfig, ShardingStrategy};
+pub use split_batch_step::SplitBatchStep;
+pub use timed_buffer_step::{Mergeable, TimedBufferConfig, TimedBufferStep};
 pub use transaction_stream_step::TransactionStreamStep;
 pub use version_tracker_step::{
     ProcessorStatusSaver, VersionTrackerStep, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS,
 };
-pub use write_rate_limit_step::{Sizeable, WriteRateLimitConfig, WriteRateLimitStep};
+#[cfg(feature = "webhook_sink")]
+pub use webhook_sink_step::{WebhookSinkConfig, WebhookSinkStep};
+pub use write_rate_limit_step::{
+    AdaptiveWriteRateLimitConfig, LatencyFeedback, Sizeable, WriteRateLimitConfig,
+    WriteRateLimitFeedback, WriteRateLimitStep,
+};
@@ -3,15 +3,50 @@ use crate::{
         pollable_async_step::PollableAsyncRunType, NamedStep, PollableAsyncStep, Processable,
     },
     types::transaction_context::TransactionContext,
-    utils::errors::ProcessorError,
+    utils::{
+        errors::ProcessorError,
+        step_metrics::{
+            StepMetricLabels, ORDER_BY_VERSION_STEP_BUFFERED_BATCHES,
+            ORDER_BY_VERSION_STEP_BUFFERED_BYTES, ORDER_BY_VERSION_STEP_GAP_AGE_SECS,
+        },
+    },
 };
 use ahash::AHashMap;
 use anyhow::Result;
 use async_trait::async_trait;
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Limits for OrderByVersionStep. Every limit is disabled if not set.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrderByVersionConfig {
+    #[serde(default = "OrderByVersionConfig::default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Maximum number of out of order batches to buffer. Once reached, the next out of order
+    /// batch fails the step with an error naming the missing versions.
+    #[serde(default)]
+    pub max_buffered_batches_before_failure: Option<usize>,
+    /// Like `max_buffered_batches_before_failure`, but for the batches' `total_size_in_bytes`.
+    #[serde(default)]
+    pub max_buffered_bytes_before_failure: Option<u64>,
+    /// The step fails once a gap has been open for this long.
+    #[serde(default)]
+    pub gap_timeout_secs: Option<u64>,
+}
+
+impl OrderByVersionConfig {
+    pub const fn default_poll_interval_ms() -> u64 {
+        1000
+    }
+}
 
 /// OrderByVersionStep is a step that orders TransactionContexts by their starting versions.
 /// It buffers ordered TransactionContexts and releases them at every poll_interval.
+///
+/// The step never waits for a gap to close while processing, as the batch closing it has to
+/// come through the same input channel. Instead, a full buffer or an expired gap timeout fails
+/// the step, so that the processor restarts from its last checkpoint.
 pub struct OrderByVersionStep<Input>
 where
     Self: Sized + Send + 'static,
@@ -22,6 +57,12 @@ where
     pub expected_next_version: u64,
     // Duration to poll and return the ordered versions
     pub poll_interval: Duration,
+    max_buffered_batches_before_failure: Option<usize>,
+    max_buffered_bytes_before_failure: Option<u64>,
+    gap_timeout: Option<Duration>,
+    buffered_bytes: u64,
+    // When the current gap at `expected_next_version` was detected.
+    gap_opened_at: Option<Instant>,
 }
 
 impl<Input> OrderByVersionStep<Input>
@@ -35,6 +76,23 @@ where
             unordered_versions: AHashMap::new(),
             expected_next_version: starting_version,
             poll_interval,
+            max_buffered_batches_before_failure: None,
+            max_buffered_bytes_before_failure: None,
+            gap_timeout: None,
+            buffered_bytes: 0,
+            gap_opened_at: None,
+        }
+    }
+
+    pub fn new_with_config(starting_version: u64, config: OrderByVersionConfig) -> Self {
+        Self {
+            max_buffered_batches_before_failure: config.max_buffered_batches_before_failure,
+            max_buffered_bytes_before_failure: config.max_buffered_bytes_before_failure,
+            gap_timeout: config.gap_timeout_secs.map(Duration::from_secs),
+            ..Self::new(
+                starting_version,
+                Duration::from_millis(config.poll_interval_ms),
+            )
         }
     }
 
@@ -44,9 +102,68 @@ where
             .unordered_versions
             .remove(&(self.expected_next_version))
         {
+            self.buffered_bytes -= batch.metadata.total_size_in_bytes;
             self.expected_next_version = batch.metadata.end_version + 1;
             self.ordered_versions.push(batch);
         }
+        // Any remaining batches are behind a new gap.
+        self.gap_opened_at = (!self.unordered_versions.is_empty()).then(Instant::now);
+    }
+
+    fn is_buffer_full(&self) -> bool {
+        self.max_buffered_batches_before_failure
+            .is_some_and(|max_batches| self.unordered_versions.len() >= max_batches)
+            || self
+                .max_buffered_bytes_before_failure
+                .is_some_and(|max_bytes| self.buffered_bytes >= max_bytes)
+    }
+
+    /// Returns an error naming the missing versions if the current gap is open for too long.
+    fn check_gap_timeout(&self) -> Result<(), ProcessorError> {
+        let (Some(gap_timeout), Some(gap_opened_at)) = (self.gap_timeout, self.gap_opened_at)
+        else {
+            return Ok(());
+        };
+        if gap_opened_at.elapsed() < gap_timeout {
+            return Ok(());
+        }
+        Err(self.missing_versions_error(&format!("after waiting {:?}", gap_timeout)))
+    }
+
+    fn missing_versions_error(&self, reason: &str) -> ProcessorError {
+        let next_buffered_version = self
+            .unordered_versions
+            .keys()
+            .min()
+            .copied()
+            .unwrap_or(self.expected_next_version);
+        ProcessorError::ProcessError {
+            message: format!(
+                "Versions {} to {} are missing {}",
+                self.expected_next_version,
+                next_buffered_version.saturating_sub(1),
+                reason
+            ),
+        }
+    }
+
+    fn update_metrics(&self) {
+        let labels = StepMetricLabels {
+            step_name: self.name(),
+        };
+        ORDER_BY_VERSION_STEP_BUFFERED_BATCHES
+            .get_or_create(&labels)
+            .set(self.unordered_versions.len() as i64);
+        ORDER_BY_VERSION_STEP_BUFFERED_BYTES
+            .get_or_create(&labels)
+            .set(self.buffered_bytes as i64);
+        ORDER_BY_VERSION_STEP_GAP_AGE_SECS
+            .get_or_create(&labels)
+            .set(
+                self.gap_opened_at
+                    .map(|gap_opened_at| gap_opened_at.elapsed().as_secs_f64())
+                    .unwrap_or_default(),
+            );
     }
 }
 
@@ -72,6 +189,18 @@ where
                 "Gap detected starting from version: {}",
                 current_batch.metadata.start_version
             );
+            // The batch closing the gap can only arrive after this one, so waiting here for
+            // the buffer to drain would never finish.
+            if self.is_buffer_full() {
+                self.update_metrics();
+                return Err(self.missing_versions_error(&format!(
+                    "and the out of order buffer is full at {} batches and {} bytes",
+                    self.unordered_versions.len(),
+                    self.buffered_bytes
+                )));
+            }
+            self.gap_opened_at.get_or_insert_with(Instant::now);
+            self.buffered_bytes += current_batch.metadata.total_size_in_bytes;
             self.unordered_versions
                 .insert(current_batch.metadata.start_version, current_batch);
         } else {
@@ -82,8 +211,7 @@ where
             // If the current_versions is the expected_next_version, update the ordered_versions
             self.update_ordered_versions();
         }
-
-        // TODO: Consider adding a metric for the number of unordered_versions for debugging, performance tesing
+        self.update_metrics();
 
         // Pass through
         Ok(None) // No immediate output
@@ -104,6 +232,8 @@ impl<Input: Send + Sync + 'static> PollableAsyncStep for OrderByVersionStep<Inpu
     }
 
     async fn poll(&mut self) -> Result<Option<Vec<TransactionContext<Input>>>, ProcessorError> {
+        self.update_metrics();
+        self.check_gap_timeout()?;
         Ok(Some(std::mem::take(&mut self.ordered_versions)))
     }
 }
@@ -136,6 +266,7 @@ mod tests {
                     start_transaction_timestamp: None,
                     end_transaction_timestamp: None,
                     total_size_in_bytes: 0,
+                    span: Default::default(),
                 },
             },
             TransactionContext {
@@ -146,6 +277,7 @@ mod tests {
                     start_transaction_timestamp: None,
                     end_transaction_timestamp: None,
                     total_size_in_bytes: 0,
+                    span: Default::default(),
                 },
             },
         ]
@@ -186,4 +318,55 @@ mod tests {
             );
         }
     }
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<()> {
+        TransactionContext {
+            data: (),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_gap_timeout_names_missing_versions() {
+        let mut step = OrderByVersionStep::<()>::new_with_config(0, OrderByVersionConfig {
+            poll_interval_ms: 10,
+            max_buffered_batches_before_failure: None,
+            max_buffered_bytes_before_failure: None,
+            gap_timeout_secs: Some(0),
+        });
+        step.process(batch(100, 199)).await.unwrap();
+        let error = step.poll().await.unwrap_err();
+        assert!(error.to_string().contains("Versions 0 to 99 are missing"));
+
+        // The missing batch is still accepted and releases the buffered one.
+        step.process(batch(0, 99)).await.unwrap();
+        assert!(step.unordered_versions.is_empty());
+        let outputs = step.poll().await.unwrap().unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(step.expected_next_version, 200);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_full_buffer_fails_without_waiting() {
+        let mut step = OrderByVersionStep::<()>::new_with_config(0, OrderByVersionConfig {
+            poll_interval_ms: 10,
+            max_buffered_batches_before_failure: Some(1),
+            max_buffered_bytes_before_failure: None,
+            gap_timeout_secs: None,
+        });
+        step.process(batch(100, 199)).await.unwrap();
+        // Without a gap timeout this would hang if the step waited for the buffer to drain.
+        let error = tokio::time::timeout(Duration::from_secs(1), step.process(batch(200, 299)))
+            .await
+            .expect("process must not wait for the gap to close")
+            .unwrap_err();
+        assert!(error.to_string().contains("Versions 0 to 99 are missing"));
+        assert_eq!(step.unordered_versions.len(), 1);
+    }
 }
@@ -0,0 +1,352 @@
+use crate::{
+    traits::{
+        pollable_async_step::PollableAsyncRunType, NamedStep, PollableAsyncStep, Processable,
+    },
+    types::transaction_context::{TransactionContext, TransactionMetadata},
+    utils::errors::ProcessorError,
+};
+use anyhow::Result;
+use arrow::{datatypes::SchemaRef, json::ReaderBuilder};
+use async_trait::async_trait;
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::info;
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Rows written by `ParquetSinkStep`. Rows are serialized with serde, so the field names and
+/// types must match the Arrow schema.
+pub trait ParquetSchema: Serialize {
+    fn arrow_schema() -> SchemaRef;
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParquetSinkConfig {
+    pub output_dir: PathBuf,
+    /// Files are named `{file_prefix}_{start_version}_{end_version}.parquet`.
+    pub file_prefix: String,
+    #[serde(default = "ParquetSinkConfig::default_max_row_group_rows")]
+    pub max_row_group_rows: usize,
+    /// The current file is closed once it reaches this size.
+    #[serde(default = "ParquetSinkConfig::default_max_file_size_bytes")]
+    pub max_file_size_bytes: usize,
+    /// The current file is closed once it has been open for this long, so that progress is
+    /// checkpointed regularly even when little data comes in.
+    #[serde(default = "ParquetSinkConfig::default_max_file_age_secs")]
+    pub max_file_age_secs: u64,
+}
+
+impl ParquetSinkConfig {
+    pub const fn default_max_row_group_rows() -> usize {
+        100_000
+    }
+
+    pub const fn default_max_file_size_bytes() -> usize {
+        256 * 1024 * 1024
+    }
+
+    pub const fn default_max_file_age_secs() -> u64 {
+        300
+    }
+}
+
+struct OpenFile {
+    writer: ArrowWriter<File>,
+    path: PathBuf,
+}
+
+/// Writes rows to rolling Parquet files.
+///
+/// Rows are buffered into row groups of `max_row_group_rows`. Once a file is closed and
+/// synced to disk, the step outputs a single context covering every batch written to it,
+/// so a downstream `VersionTrackerStep` only checkpoints versions that are persisted.
+pub struct ParquetSinkStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: ParquetSchema + Send + 'static,
+{
+    config: ParquetSinkConfig,
+    schema: SchemaRef,
+    buffer: Vec<T>,
+    open_file: Option<OpenFile>,
+    // Metadata of every batch received since the last file was closed.
+    pending_metadata: Option<TransactionMetadata>,
+    pending_since: Instant,
+}
+
+impl<T> ParquetSinkStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: ParquetSchema + Send + 'static,
+{
+    pub fn new(config: ParquetSinkConfig) -> Result<Self, ProcessorError> {
+        std::fs::create_dir_all(&config.output_dir).map_err(|e| ProcessorError::StepInitError {
+            message: format!(
+                "Failed to create output directory {:?}: {e:?}",
+                config.output_dir
+            ),
+        })?;
+        Ok(Self {
+            config,
+            schema: T::arrow_schema(),
+            buffer: Vec::new(),
+            open_file: None,
+            pending_metadata: None,
+            pending_since: Instant::now(),
+        })
+    }
+
+    fn open_new_file(&self, start_version: u64) -> Result<OpenFile, ProcessorError> {
+        let path = self.config.output_dir.join(format!(
+            "{}_{start_version}.parquet.inprogress",
+            self.config.file_prefix
+        ));
+        let file = File::create(&path).map_err(|e| ProcessorError::ProcessError {
+            message: format!("Failed to create parquet file {path:?}: {e:?}"),
+        })?;
+        let props = WriterProperties::builder()
+            .set_max_row_group_size(self.config.max_row_group_rows)
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let writer = ArrowWriter::try_new(file, self.schema.clone(), Some(props)).map_err(|e| {
+            ProcessorError::ProcessError {
+                message: format!("Failed to create parquet writer: {e:?}"),
+            }
+        })?;
+        Ok(OpenFile { writer, path })
+    }
+
+    /// Writes the buffered rows to the current file as a row group.
+    async fn write_row_group(&mut self) -> Result<(), ProcessorError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let mut open_file = match self.open_file.take() {
+            Some(open_file) => open_file,
+            None => self.open_new_file(
+                self.pending_metadata
+                    .as_ref()
+                    .map(|metadata| metadata.start_version)
+                    .unwrap_or_default(),
+            )?,
+        };
+        let rows = std::mem::take(&mut self.buffer);
+        let schema = self.schema.clone();
+        // Encoding and writing are blocking, so they run off the async runtime.
+        let open_file = tokio::task::spawn_blocking(move || {
+            let mut decoder = ReaderBuilder::new(schema)
+                .build_decoder()
+                .and_then(|mut decoder| decoder.serialize(&rows).map(|_| decoder))
+                .map_err(|e| ProcessorError::ProcessError {
+                    message: format!("Failed to convert rows to arrow: {e:?}"),
+                })?;
+            let batch = decoder.flush().map_err(|e| ProcessorError::ProcessError {
+                message: format!("Failed to convert rows to arrow: {e:?}"),
+            })?;
+            if let Some(batch) = batch {
+                open_file
+                    .writer
+                    .write(&batch)
+                    .and_then(|_| open_file.writer.flush())
+                    .map_err(|e| ProcessorError::ProcessError {
+                        message: format!("Failed to write row group: {e:?}"),
+                    })?;
+            }
+            Ok(open_file)
+        })
+        .await
+        .expect("Task panicked writing row group")?;
+        self.open_file = Some(open_file);
+        Ok(())
+    }
+
+    fn current_file_size(&self) -> usize {
+        self.open_file
+            .as_ref()
+            .map(|open_file| open_file.writer.bytes_written() + open_file.writer.in_progress_size())
+            .unwrap_or_default()
+    }
+
+    /// Closes the current file and returns the context covering everything written to it.
+    async fn close_file(&mut self) -> Result<Option<TransactionContext<()>>, ProcessorError> {
+        self.write_row_group().await?;
+        let Some(metadata) = self.pending_metadata.take() else {
+            return Ok(None);
+        };
+        // Batches without rows don't produce a file, but their versions are still done.
+        if let Some(open_file) = self.open_file.take() {
+            let final_path = self.config.output_dir.join(format!(
+                "{}_{}_{}.parquet",
+                self.config.file_prefix, metadata.start_version, metadata.end_version
+            ));
+            tokio::task::spawn_blocking(move || finish_file(open_file, &final_path))
+                .await
+                .expect("Task panicked closing parquet file")?;
+        }
+        Ok(Some(TransactionContext { data: (), metadata }))
+    }
+}
+
+/// Writes the footer, syncs the file and moves it to its final name.
+fn finish_file(open_file: OpenFile, final_path: &Path) -> Result<(), ProcessorError> {
+    let to_error = |e: String| ProcessorError::ProcessError {
+        message: format!("Failed to close parquet file {final_path:?}: {e}"),
+    };
+    let file = open_file
+        .writer
+        .into_inner()
+        .map_err(|e| to_error(format!("{e:?}")))?;
+    file.sync_all().map_err(|e| to_error(format!("{e:?}")))?;
+    std::fs::rename(&open_file.path, final_path).map_err(|e| to_error(format!("{e:?}")))?;
+    #[cfg(unix)]
+    if let Some(dir) = final_path.parent() {
+        File::open(dir)
+            .and_then(|dir| dir.sync_all())
+            .map_err(|e| to_error(format!("{e:?}")))?;
+    }
+    info!(path = ?final_path, "Closed parquet file");
+    Ok(())
+}
+
+#[async_trait]
+impl<T> Processable for ParquetSinkStep<T>
+where
+    T: ParquetSchema + Send + Sync + 'static,
+{
+    type Input = Vec<T>;
+    type Output = ();
+    type RunType = PollableAsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<T>>,
+    ) -> Result<Option<TransactionContext<()>>, ProcessorError> {
+        match self.pending_metadata.as_mut() {
+            Some(metadata) => metadata.extend(&item.metadata),
+            None => {
+                self.pending_metadata = Some(item.metadata);
+                self.pending_since = Instant::now();
+            },
+        }
+        self.buffer.extend(item.data);
+        if self.buffer.len() >= self.config.max_row_group_rows {
+            self.write_row_group().await?;
+        }
+        if self.current_file_size() >= self.config.max_file_size_bytes {
+            return self.close_file().await;
+        }
+        Ok(None)
+    }
+
+    async fn cleanup(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        Ok(self.close_file().await?.map(|context| vec![context]))
+    }
+}
+
+#[async_trait]
+impl<T> PollableAsyncStep for ParquetSinkStep<T>
+where
+    T: ParquetSchema + Send + Sync + 'static,
+{
+    fn poll_interval(&self) -> Duration {
+        POLL_INTERVAL
+    }
+
+    async fn poll(&mut self) -> Result<Option<Vec<TransactionContext<()>>>, ProcessorError> {
+        if self.pending_metadata.is_none()
+            || self.pending_since.elapsed() < Duration::from_secs(self.config.max_file_age_secs)
+        {
+            return Ok(None);
+        }
+        Ok(self.close_file().await?.map(|context| vec![context]))
+    }
+}
+
+impl<T> NamedStep for ParquetSinkStep<T>
+where
+    T: ParquetSchema + Send + 'static,
+{
+    fn name(&self) -> String {
+        format!("ParquetSinkStep: {}", std::any::type_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::sync::Arc;
+
+    #[derive(Serialize)]
+    struct TestRow {
+        version: u64,
+        name: String,
+    }
+
+    impl ParquetSchema for TestRow {
+        fn arrow_schema() -> SchemaRef {
+            Arc::new(Schema::new(vec![
+                Field::new("version", DataType::UInt64, false),
+                Field::new("name", DataType::Utf8, false),
+            ]))
+        }
+    }
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<Vec<TestRow>> {
+        TransactionContext {
+            data: (start_version..=end_version)
+                .map(|version| TestRow {
+                    version,
+                    name: format!("row {version}"),
+                })
+                .collect(),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_parquet_sink_reports_versions_after_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut step = ParquetSinkStep::<TestRow>::new(ParquetSinkConfig {
+            output_dir: dir.path().to_path_buf(),
+            file_prefix: "test".to_string(),
+            max_row_group_rows: 4,
+            max_file_size_bytes: ParquetSinkConfig::default_max_file_size_bytes(),
+            max_file_age_secs: ParquetSinkConfig::default_max_file_age_secs(),
+        })
+        .unwrap();
+
+        // Nothing is reported until the file is closed.
+        assert!(step.process(batch(0, 4)).await.unwrap().is_none());
+        assert!(step.process(batch(5, 9)).await.unwrap().is_none());
+
+        let outputs = step.cleanup().await.unwrap().unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].metadata.start_version, 0);
+        assert_eq!(outputs[0].metadata.end_version, 9);
+
+        let file = File::open(dir.path().join("test_0_9.parquet")).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file).unwrap();
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 10);
+        assert!(reader.metadata().num_row_groups() >= 2);
+        assert!(!dir.path().join("test_0.parquet.inprogress").exists());
+    }
+}
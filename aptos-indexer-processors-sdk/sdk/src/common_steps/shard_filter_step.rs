@@ -0,0 +1,228 @@
+use crate::{
+    traits::{AsyncRunType, AsyncStep, NamedStep, Processable},
+    types::transaction_context::TransactionContext,
+    utils::{convert::standardize_address, errors::ProcessorError},
+};
+use anyhow::Result;
+use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// How data is partitioned between the shards of a processor.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShardingStrategy {
+    /// Each user transaction belongs to the shard its sender address hashes to. Transactions
+    /// without a sender (genesis, block metadata, ...) belong to shard 0.
+    #[default]
+    SenderAddress,
+    /// Every shard sees every transaction, but only keeps the events whose type hashes to it.
+    EventType,
+}
+
+/// Configuration for running one shard out of `num_shards` instances of the same processor.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShardingConfig {
+    pub num_shards: u64,
+    /// Index of the shard owned by this instance, in `0..num_shards`.
+    pub shard_index: u64,
+    #[serde(default)]
+    pub strategy: ShardingStrategy,
+}
+
+impl ShardingConfig {
+    /// Name under which this shard tracks its own progress, e.g. `processor_name#shard-2`.
+    pub fn shard_processor_name(&self, processor_name: &str) -> String {
+        shard_processor_name(processor_name, self.shard_index)
+    }
+
+    pub fn owns_key(&self, key: &str) -> bool {
+        shard_for_key(key, self.num_shards) == self.shard_index
+    }
+
+    pub fn validate(&self) -> Result<(), ProcessorError> {
+        if self.num_shards == 0 || self.shard_index >= self.num_shards {
+            return Err(ProcessorError::StepInitError {
+                message: format!(
+                    "Invalid sharding config: shard_index {} is not in [0, {})",
+                    self.shard_index, self.num_shards
+                ),
+            });
+        }
+        Ok(())
+    }
+}
+
+pub fn shard_processor_name(processor_name: &str, shard_index: u64) -> String {
+    format!("{processor_name}#shard-{shard_index}")
+}
+
+/// Maps a key to a shard. This must be stable across builds and machines, since every
+/// instance has to agree on the partitioning.
+pub fn shard_for_key(key: &str, num_shards: u64) -> u64 {
+    let hash = Sha256::digest(key.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    u64::from_be_bytes(bytes) % num_shards
+}
+
+/// Drops the data that doesn't belong to this instance's shard, based on `ShardingConfig`.
+///
+/// The batch metadata is left untouched, so each shard still sees a contiguous range of
+/// versions and can checkpoint with `VersionTrackerStep` as usual, under its own
+/// `ShardingConfig::shard_processor_name`.
+pub struct ShardFilterStep
+where
+    Self: Sized + Send + 'static,
+{
+    sharding_config: ShardingConfig,
+}
+
+impl ShardFilterStep {
+    pub fn new(sharding_config: ShardingConfig) -> Result<Self, ProcessorError> {
+        sharding_config.validate()?;
+        Ok(Self { sharding_config })
+    }
+
+    fn owns_transaction(&self, transaction: &Transaction) -> bool {
+        let sender = match transaction.txn_data.as_ref() {
+            Some(TxnData::User(user_transaction)) => user_transaction
+                .request
+                .as_ref()
+                .map(|request| standardize_address(&request.sender)),
+            _ => None,
+        };
+        match sender {
+            Some(sender) => self.sharding_config.owns_key(&sender),
+            None => self.sharding_config.shard_index == 0,
+        }
+    }
+
+    fn retain_owned_events(&self, transaction: &mut Transaction) {
+        let events = match transaction.txn_data.as_mut() {
+            Some(TxnData::BlockMetadata(tx_inner)) => &mut tx_inner.events,
+            Some(TxnData::Genesis(tx_inner)) => &mut tx_inner.events,
+            Some(TxnData::User(tx_inner)) => &mut tx_inner.events,
+            _ => return,
+        };
+        events.retain(|event| self.sharding_config.owns_key(&event.type_str));
+    }
+}
+
+#[async_trait]
+impl Processable for ShardFilterStep {
+    type Input = Vec<Transaction>;
+    type Output = Vec<Transaction>;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<Transaction>>,
+    ) -> Result<Option<TransactionContext<Vec<Transaction>>>, ProcessorError> {
+        let data = match self.sharding_config.strategy {
+            ShardingStrategy::SenderAddress => item
+                .data
+                .into_iter()
+                .filter(|transaction| self.owns_transaction(transaction))
+                .collect(),
+            ShardingStrategy::EventType => {
+                let mut transactions = item.data;
+                for transaction in transactions.iter_mut() {
+                    self.retain_owned_events(transaction);
+                }
+                transactions
+            },
+        };
+        Ok(Some(TransactionContext {
+            data,
+            metadata: item.metadata,
+        }))
+    }
+}
+
+impl AsyncStep for ShardFilterStep {}
+
+impl NamedStep for ShardFilterStep {
+    fn name(&self) -> String {
+        format!(
+            "ShardFilterStep: {}/{}",
+            self.sharding_config.shard_index, self.sharding_config.num_shards
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction_context::TransactionMetadata;
+    use aptos_protos::transaction::v1::{UserTransaction, UserTransactionRequest};
+
+    fn user_transaction(version: u64, sender: &str) -> Transaction {
+        Transaction {
+            version,
+            txn_data: Some(TxnData::User(UserTransaction {
+                request: Some(UserTransactionRequest {
+                    sender: sender.to_string(),
+                    ..UserTransactionRequest::default()
+                }),
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_shards_partition_transactions_by_sender() {
+        let transactions = (0..50)
+            .map(|i| user_transaction(i, &format!("0x{i:x}")))
+            .chain(std::iter::once(Transaction {
+                version: 50,
+                ..Transaction::default()
+            }))
+            .collect::<Vec<_>>();
+        let num_shards = 3;
+
+        let mut seen_versions = vec![];
+        for shard_index in 0..num_shards {
+            let mut step = ShardFilterStep::new(ShardingConfig {
+                num_shards,
+                shard_index,
+                strategy: ShardingStrategy::SenderAddress,
+            })
+            .unwrap();
+            let result = step
+                .process(TransactionContext {
+                    data: transactions.clone(),
+                    metadata: TransactionMetadata {
+                        start_version: 0,
+                        end_version: 50,
+                        ..TransactionMetadata::default()
+                    },
+                })
+                .await
+                .unwrap()
+                .unwrap();
+            // Every shard keeps the full version range.
+            assert_eq!(result.metadata.start_version, 0);
+            assert_eq!(result.metadata.end_version, 50);
+            seen_versions.extend(result.data.iter().map(|t| t.version));
+        }
+
+        // Every transaction belongs to exactly one shard.
+        seen_versions.sort();
+        assert_eq!(seen_versions, (0..=50).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_invalid_sharding_config() {
+        assert!(ShardFilterStep::new(ShardingConfig {
+            num_shards: 2,
+            shard_index: 2,
+            strategy: ShardingStrategy::SenderAddress,
+        })
+        .is_err());
+    }
+}
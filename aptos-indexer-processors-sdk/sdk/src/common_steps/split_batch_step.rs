@@ -0,0 +1,233 @@
+use crate::{
+    traits::{
+        pollable_async_step::PollableAsyncRunType, NamedStep, PollableAsyncStep, Processable,
+    },
+    types::transaction_context::{TransactionContext, TransactionMetadata},
+    utils::errors::ProcessorError,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::VecDeque, time::Duration};
+
+// Split batches are normally emitted straight away; polling only drains what's left over.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Splits batches with more than `max_items` items into smaller batches.
+///
+/// Batches are only split between versions, so all items of a version stay together and a
+/// batch can still go over `max_items` if a single version has more items. The split batches
+/// cover the input's versions without gaps: versions without items are folded into the next
+/// batch. Input items must be ordered by version.
+///
+/// Timestamps of the versions in between aren't known, so every split batch but the last
+/// uses the input's start timestamp as both its start and end timestamp. This never
+/// overstates progress.
+pub struct SplitBatchStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Send + 'static,
+{
+    max_items: usize,
+    version_fn: Box<dyn Fn(&T) -> u64 + Send + Sync>,
+    // Split batches that couldn't be returned from `process` yet.
+    ready: VecDeque<TransactionContext<Vec<T>>>,
+}
+
+impl<T> SplitBatchStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Send + 'static,
+{
+    /// `version_fn` returns the transaction version an item was extracted from.
+    pub fn new<F>(max_items: usize, version_fn: F) -> Self
+    where
+        F: Fn(&T) -> u64 + Send + Sync + 'static,
+    {
+        Self {
+            max_items: max_items.max(1),
+            version_fn: Box::new(version_fn),
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn split(&self, item: TransactionContext<Vec<T>>) -> Vec<TransactionContext<Vec<T>>> {
+        if item.data.len() <= self.max_items {
+            return vec![item];
+        }
+        let metadata = item.metadata;
+        let num_items = item.data.len() as u64;
+
+        let mut chunks: Vec<Vec<T>> = vec![];
+        let mut current: Vec<T> = vec![];
+        for row in item.data {
+            let version = (self.version_fn)(&row);
+            let is_new_version = current
+                .last()
+                .is_some_and(|last| (self.version_fn)(last) != version);
+            if current.len() >= self.max_items && is_new_version {
+                chunks.push(std::mem::take(&mut current));
+            }
+            current.push(row);
+        }
+        chunks.push(current);
+
+        let num_chunks = chunks.len();
+        let mut next_start_version = metadata.start_version;
+        chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| {
+                let is_last = index + 1 == num_chunks;
+                let end_version = if is_last {
+                    metadata.end_version
+                } else {
+                    data.last()
+                        .map(|row| (self.version_fn)(row))
+                        .unwrap_or_default()
+                };
+                let chunk_metadata = TransactionMetadata {
+                    start_version: next_start_version,
+                    end_version,
+                    start_transaction_timestamp: metadata.start_transaction_timestamp.clone(),
+                    end_transaction_timestamp: if is_last {
+                        metadata.end_transaction_timestamp.clone()
+                    } else {
+                        metadata.start_transaction_timestamp.clone()
+                    },
+                    // Attribute the size proportionally to the number of items.
+                    total_size_in_bytes: metadata.total_size_in_bytes * data.len() as u64
+                        / num_items,
+                    span: metadata.span.clone(),
+                };
+                next_start_version = end_version + 1;
+                TransactionContext {
+                    data,
+                    metadata: chunk_metadata,
+                }
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl<T> Processable for SplitBatchStep<T>
+where
+    T: Send + Sync + 'static,
+{
+    type Input = Vec<T>;
+    type Output = Vec<T>;
+    type RunType = PollableAsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<T>>,
+    ) -> Result<Option<TransactionContext<Vec<T>>>, ProcessorError> {
+        let chunks = self.split(item);
+        self.ready.extend(chunks);
+        // Earlier leftovers go first to keep batches in order.
+        Ok(self.ready.pop_front())
+    }
+
+    async fn cleanup(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        Ok(Some(self.ready.drain(..).collect()))
+    }
+}
+
+#[async_trait]
+impl<T> PollableAsyncStep for SplitBatchStep<T>
+where
+    T: Send + Sync + 'static,
+{
+    fn poll_interval(&self) -> Duration {
+        POLL_INTERVAL
+    }
+
+    async fn poll(&mut self) -> Result<Option<Vec<TransactionContext<Vec<T>>>>, ProcessorError> {
+        Ok(Some(self.ready.drain(..).collect()))
+    }
+}
+
+impl<T> NamedStep for SplitBatchStep<T>
+where
+    T: Send + 'static,
+{
+    fn name(&self) -> String {
+        format!("SplitBatchStep: {}", std::any::type_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::util::timestamp::Timestamp;
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_split_batch_along_versions() {
+        let mut step = SplitBatchStep::new(3, |(version, _): &(u64, u64)| *version);
+        // Versions 0 to 9, with two items for each even version.
+        let data = (0..10u64)
+            .filter(|version| version % 2 == 0)
+            .flat_map(|version| [(version, 0), (version, 1)])
+            .collect::<Vec<_>>();
+        let item = TransactionContext {
+            data,
+            metadata: TransactionMetadata {
+                start_version: 0,
+                end_version: 9,
+                start_transaction_timestamp: Some(Timestamp {
+                    seconds: 1,
+                    nanos: 0,
+                }),
+                end_transaction_timestamp: Some(Timestamp {
+                    seconds: 2,
+                    nanos: 0,
+                }),
+                total_size_in_bytes: 100,
+                span: Default::default(),
+            },
+        };
+
+        let mut outputs = vec![step.process(item).await.unwrap().unwrap()];
+        outputs.extend(step.poll().await.unwrap().unwrap());
+        let ranges = outputs
+            .iter()
+            .map(|output| {
+                (
+                    output.metadata.start_version,
+                    output.metadata.end_version,
+                    output.data.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+        // Items of a version are never split, and the ranges are contiguous.
+        assert_eq!(ranges, vec![(0, 2, 4), (3, 6, 4), (7, 9, 2)]);
+        assert_eq!(
+            outputs
+                .iter()
+                .map(|output| output.metadata.total_size_in_bytes)
+                .sum::<u64>(),
+            100
+        );
+        assert_eq!(
+            outputs[0]
+                .metadata
+                .end_transaction_timestamp
+                .as_ref()
+                .unwrap()
+                .seconds,
+            1
+        );
+        assert_eq!(
+            outputs[2]
+                .metadata
+                .end_transaction_timestamp
+                .as_ref()
+                .unwrap()
+                .seconds,
+            2
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    common_steps::Sizeable,
     traits::{
         pollable_async_step::PollableAsyncRunType, NamedStep, PollableAsyncStep, Processable,
     },
@@ -7,8 +8,90 @@ use crate::{
 };
 use anyhow::Result;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+/// Config for a TimedBufferStep that also flushes on size. Any trigger that is set flushes
+/// the buffer as soon as it's reached, without waiting for the next poll.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimedBufferConfig {
+    #[serde(default = "TimedBufferConfig::default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Flush once the buffer holds this many items.
+    #[serde(default)]
+    pub max_items: Option<usize>,
+    /// Flush once the buffered items are this big, as measured by `Sizeable`.
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    /// Flush once the buffer covers this many transaction versions.
+    #[serde(default)]
+    pub max_version_span: Option<u64>,
+}
+
+impl TimedBufferConfig {
+    pub const fn default_poll_interval_ms() -> u64 {
+        1000
+    }
+}
+
+/// Data that TimedBufferStep can count and merge, so that contiguous batches are emitted
+/// as a single batch.
+pub trait Mergeable: Sizeable {
+    fn num_items(&self) -> usize;
+
+    /// Appends `other`, the data of the batch that directly follows this one.
+    fn merge(&mut self, other: Self);
+}
+
+impl<T: Sizeable> Mergeable for Vec<T> {
+    fn num_items(&self) -> usize {
+        self.len()
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.extend(other);
+    }
+}
+
+// Keeps the struct free of the Mergeable bound, so plain time-based buffering works for any
+// input.
+struct FlushTriggers<Input> {
+    config: TimedBufferConfig,
+    num_items: fn(&Input) -> usize,
+    size_in_bytes: fn(&Input) -> u64,
+    merge: fn(&mut Input, Input),
+    buffered_items: usize,
+    buffered_bytes: u64,
+}
+
+impl<Input> FlushTriggers<Input> {
+    fn should_flush(&self, buffer: &[TransactionContext<Input>]) -> bool {
+        let version_span = match (buffer.first(), buffer.last()) {
+            (Some(first), Some(last)) => {
+                (last.metadata.end_version + 1).saturating_sub(first.metadata.start_version)
+            },
+            _ => 0,
+        };
+        self.config
+            .max_items
+            .is_some_and(|max_items| self.buffered_items >= max_items)
+            || self
+                .config
+                .max_bytes
+                .is_some_and(|max_bytes| self.buffered_bytes >= max_bytes)
+            || self
+                .config
+                .max_version_span
+                .is_some_and(|max_version_span| version_span >= max_version_span)
+    }
+
+    fn remove(&mut self, context: &TransactionContext<Input>) {
+        self.buffered_items -= (self.num_items)(&context.data);
+        self.buffered_bytes -= (self.size_in_bytes)(&context.data);
+    }
+}
+
 pub struct TimedBufferStep<Input>
 where
     Self: Sized + Send + 'static,
@@ -16,6 +99,7 @@ where
 {
     pub internal_buffer: Vec<TransactionContext<Input>>,
     pub poll_interval: Duration,
+    flush_triggers: Option<FlushTriggers<Input>>,
 }
 
 impl<Input> TimedBufferStep<Input>
@@ -28,8 +112,37 @@ where
         Self {
             internal_buffer: Vec::new(),
             poll_interval,
+            flush_triggers: None,
+        }
+    }
+
+    /// Buffers and merges contiguous batches, flushing on the triggers in `config` as well as
+    /// every poll interval.
+    pub fn new_with_config(config: TimedBufferConfig) -> Self
+    where
+        Input: Mergeable,
+    {
+        Self {
+            internal_buffer: Vec::new(),
+            poll_interval: Duration::from_millis(config.poll_interval_ms),
+            flush_triggers: Some(FlushTriggers {
+                config,
+                num_items: Input::num_items,
+                size_in_bytes: Input::size_in_bytes,
+                merge: Input::merge,
+                buffered_items: 0,
+                buffered_bytes: 0,
+            }),
         }
     }
+
+    fn take_buffer(&mut self) -> Vec<TransactionContext<Input>> {
+        if let Some(flush_triggers) = self.flush_triggers.as_mut() {
+            flush_triggers.buffered_items = 0;
+            flush_triggers.buffered_bytes = 0;
+        }
+        std::mem::take(&mut self.internal_buffer)
+    }
 }
 
 #[async_trait]
@@ -45,15 +158,36 @@ where
         &mut self,
         item: TransactionContext<Input>,
     ) -> Result<Option<TransactionContext<Input>>, ProcessorError> {
-        self.internal_buffer.push(item);
-        Ok(None) // No immediate output
+        let Some(flush_triggers) = self.flush_triggers.as_mut() else {
+            self.internal_buffer.push(item);
+            return Ok(None); // No immediate output
+        };
+
+        flush_triggers.buffered_items += (flush_triggers.num_items)(&item.data);
+        flush_triggers.buffered_bytes += (flush_triggers.size_in_bytes)(&item.data);
+        match self.internal_buffer.last_mut() {
+            Some(last) if last.metadata.end_version + 1 == item.metadata.start_version => {
+                last.metadata.extend(&item.metadata);
+                (flush_triggers.merge)(&mut last.data, item.data);
+            },
+            _ => self.internal_buffer.push(item),
+        }
+
+        if !flush_triggers.should_flush(&self.internal_buffer) {
+            return Ok(None);
+        }
+        // Only one context can be returned here. If there are gaps, the rest of the buffer
+        // goes out with the next flush.
+        let flushed = self.internal_buffer.remove(0);
+        flush_triggers.remove(&flushed);
+        Ok(Some(flushed))
     }
 
     // Once polling ends, release the remaining items in buffer
     async fn cleanup(
         &mut self,
     ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
-        Ok(Some(std::mem::take(&mut self.internal_buffer)))
+        Ok(Some(self.take_buffer()))
     }
 }
 
@@ -64,7 +198,7 @@ impl<Input: Send + Sync + 'static> PollableAsyncStep for TimedBufferStep<Input>
     }
 
     async fn poll(&mut self) -> Result<Option<Vec<TransactionContext<Input>>>, ProcessorError> {
-        Ok(Some(std::mem::take(&mut self.internal_buffer)))
+        Ok(Some(self.take_buffer()))
     }
 }
 
@@ -74,3 +208,81 @@ impl<Input: Send + 'static> NamedStep for TimedBufferStep<Input> {
         format!("TimedBuffer: {}", std::any::type_name::<Input>())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction_context::TransactionMetadata;
+
+    #[derive(Debug, PartialEq)]
+    struct TestRow(u64);
+
+    impl Sizeable for TestRow {
+        fn size_in_bytes(&self) -> u64 {
+            10
+        }
+    }
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<Vec<TestRow>> {
+        TransactionContext {
+            data: (start_version..=end_version).map(TestRow).collect(),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                total_size_in_bytes: 100,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    fn config() -> TimedBufferConfig {
+        TimedBufferConfig {
+            poll_interval_ms: TimedBufferConfig::default_poll_interval_ms(),
+            max_items: None,
+            max_bytes: None,
+            max_version_span: None,
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_flush_on_item_count_merges_batches() {
+        let mut step = TimedBufferStep::new_with_config(TimedBufferConfig {
+            max_items: Some(6),
+            ..config()
+        });
+        assert!(step.process(batch(0, 2)).await.unwrap().is_none());
+        let flushed = step.process(batch(3, 5)).await.unwrap().unwrap();
+        assert_eq!(flushed.metadata.start_version, 0);
+        assert_eq!(flushed.metadata.end_version, 5);
+        assert_eq!(flushed.metadata.total_size_in_bytes, 200);
+        assert_eq!(flushed.data.len(), 6);
+        assert!(step.internal_buffer.is_empty());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_flush_on_bytes_and_version_span() {
+        let mut step = TimedBufferStep::new_with_config(TimedBufferConfig {
+            max_bytes: Some(50),
+            ..config()
+        });
+        assert!(step.process(batch(0, 3)).await.unwrap().is_none());
+        let flushed = step.process(batch(4, 4)).await.unwrap().unwrap();
+        assert_eq!(flushed.metadata.end_version, 4);
+
+        let mut step = TimedBufferStep::new_with_config(TimedBufferConfig {
+            max_version_span: Some(100),
+            ..config()
+        });
+        assert!(step.process(batch(0, 0)).await.unwrap().is_none());
+        // Batches with a gap between them aren't merged.
+        assert!(step.process(batch(10, 10)).await.unwrap().is_none());
+        let flushed = step.process(batch(11, 99)).await.unwrap().unwrap();
+        assert_eq!(flushed.metadata.end_version, 0);
+        let remaining = step.poll().await.unwrap().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].metadata.start_version, 10);
+        assert_eq!(remaining[0].metadata.end_version, 99);
+    }
+}
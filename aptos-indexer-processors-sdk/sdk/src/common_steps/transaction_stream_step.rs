@@ -1,10 +1,15 @@
 use crate::{
     traits::{NamedStep, PollableAsyncRunType, PollableAsyncStep, Processable},
     types::transaction_context::{TransactionContext, TransactionMetadata},
-    utils::errors::ProcessorError,
+    utils::{
+        errors::ProcessorError,
+        latest_seen_timestamp::LatestSeenTimestamp,
+        pipeline_status::{pause_controller, StreamRegistration},
+    },
 };
 use anyhow::Result;
 use aptos_indexer_transaction_stream::{
+    utils::time::parse_timestamp, StreamConnectionStats,
     TransactionStream as TransactionStreamInternal, TransactionStreamConfig,
 };
 use aptos_protos::transaction::v1::Transaction;
@@ -23,6 +28,9 @@ where
 {
     transaction_stream_config: TransactionStreamConfig,
     pub transaction_stream: Mutex<TransactionStreamInternal>,
+    latest_seen_timestamp: Option<LatestSeenTimestamp>,
+    // Lists the stream's connection in the admin `/status` endpoint.
+    stream_registration: StreamRegistration,
 }
 
 impl TransactionStreamStep
@@ -39,11 +47,32 @@ where
                 message: format!("Error creating transaction stream: {e:?}"),
             }),
             Ok(transaction_stream) => Ok(Self {
+                stream_registration: StreamRegistration::new(transaction_stream.connection_stats()),
                 transaction_stream: Mutex::new(transaction_stream),
                 transaction_stream_config,
+                latest_seen_timestamp: None,
             }),
         }
     }
+
+    /// Records the stream's connection state in `connection_stats`, e.g. for
+    /// `StreamHealthChecker`.
+    pub fn with_connection_stats(mut self, connection_stats: StreamConnectionStats) -> Self {
+        self.stream_registration.update(connection_stats.clone());
+        self.transaction_stream
+            .get_mut()
+            .set_connection_stats(connection_stats);
+        self
+    }
+
+    /// Records the end timestamp of every batch received in `latest_seen_timestamp`.
+    pub fn with_latest_seen_timestamp(
+        mut self,
+        latest_seen_timestamp: LatestSeenTimestamp,
+    ) -> Self {
+        self.latest_seen_timestamp = Some(latest_seen_timestamp);
+        self
+    }
 }
 
 #[async_trait]
@@ -76,6 +105,11 @@ where
     async fn poll(
         &mut self,
     ) -> Result<Option<Vec<TransactionContext<Vec<Transaction>>>>, ProcessorError> {
+        if pause_controller().is_paused() {
+            info!("Transaction stream paused");
+            pause_controller().wait_while_paused().await;
+            info!("Transaction stream resumed");
+        }
         let txn_pb_response_res = self
             .transaction_stream
             .lock()
@@ -84,6 +118,14 @@ where
             .await;
         match txn_pb_response_res {
             Ok(txn_pb_response) => {
+                if let (Some(latest_seen_timestamp), Some(timestamp)) = (
+                    &self.latest_seen_timestamp,
+                    &txn_pb_response.end_txn_timestamp,
+                ) {
+                    latest_seen_timestamp.record(
+                        parse_timestamp(timestamp, txn_pb_response.end_version as i64).naive_utc(),
+                    );
+                }
                 let transactions_with_context = TransactionContext {
                     data: txn_pb_response.transactions,
                     metadata: TransactionMetadata {
@@ -92,6 +134,10 @@ where
                         start_transaction_timestamp: txn_pb_response.start_txn_timestamp,
                         end_transaction_timestamp: txn_pb_response.end_txn_timestamp,
                         total_size_in_bytes: txn_pb_response.size_in_bytes,
+                        span: TransactionMetadata::batch_span(
+                            txn_pb_response.start_version,
+                            txn_pb_response.end_version,
+                        ),
                     },
                 };
                 Ok(Some(vec![transactions_with_context]))
@@ -225,6 +271,7 @@ mod tests {
                     start_transaction_timestamp: None,
                     end_transaction_timestamp: None,
                     total_size_in_bytes: 10,
+                    span: Default::default(),
                 },
             }]))
         });
@@ -2,12 +2,17 @@ use crate::{
     traits::{
         pollable_async_step::PollableAsyncRunType, NamedStep, PollableAsyncStep, Processable,
     },
-    types::transaction_context::TransactionContext,
-    utils::errors::ProcessorError,
+    types::transaction_context::{TransactionContext, TransactionMetadata},
+    utils::{
+        errors::ProcessorError,
+        step_metrics::{
+            StepMetricLabels, VERSION_TRACKER_STEP_GAP_AGE_SECS, VERSION_TRACKER_STEP_GAP_COUNT,
+        },
+    },
 };
 use anyhow::Result;
 use async_trait::async_trait;
-use std::marker::PhantomData;
+use std::{collections::BTreeMap, marker::PhantomData, time::Instant};
 
 pub const DEFAULT_UPDATE_PROCESSOR_STATUS_SECS: u64 = 1;
 
@@ -22,11 +27,91 @@ pub trait ProcessorStatusSaver {
     ) -> Result<(), ProcessorError>;
 }
 
+/// Completed version ranges past the checkpoint, for the gap-tolerant mode.
+struct CompletedRanges {
+    next_version: u64,
+    // Disjoint ranges after `next_version`, keyed by start version. Adjacent ranges are merged.
+    ranges: BTreeMap<u64, TransactionMetadata>,
+    // When the checkpoint started waiting on the gap at `next_version`.
+    gap_opened_at: Option<Instant>,
+}
+
+impl CompletedRanges {
+    fn insert(&mut self, metadata: TransactionMetadata) -> Result<(), ProcessorError> {
+        let overlaps_previous = self
+            .ranges
+            .range(..=metadata.start_version)
+            .next_back()
+            .is_some_and(|(_, previous)| previous.end_version >= metadata.start_version);
+        let overlaps_next = self
+            .ranges
+            .range(metadata.start_version..)
+            .next()
+            .is_some_and(|(start_version, _)| *start_version <= metadata.end_version);
+        if metadata.start_version < self.next_version || overlaps_previous || overlaps_next {
+            return Err(ProcessorError::ProcessError {
+                message: format!(
+                    "Versions {} to {} were already processed",
+                    metadata.start_version, metadata.end_version
+                ),
+            });
+        }
+
+        let mut metadata = metadata;
+        let previous_start_version = self
+            .ranges
+            .range(..metadata.start_version)
+            .next_back()
+            .filter(|(_, previous)| previous.end_version + 1 == metadata.start_version)
+            .map(|(start_version, _)| *start_version);
+        if let Some(mut previous) =
+            previous_start_version.and_then(|start_version| self.ranges.remove(&start_version))
+        {
+            previous.extend(&metadata);
+            metadata = previous;
+        }
+        if let Some(next) = self.ranges.remove(&(metadata.end_version + 1)) {
+            metadata.extend(&next);
+        }
+        self.ranges.insert(metadata.start_version, metadata);
+        Ok(())
+    }
+
+    /// Removes and returns the range starting at `next_version`, if it's complete.
+    fn advance(&mut self) -> Option<TransactionMetadata> {
+        let completed = self.ranges.remove(&self.next_version);
+        if let Some(completed) = completed.as_ref() {
+            self.next_version = completed.end_version + 1;
+        }
+        // Any remaining ranges are behind a new gap.
+        if self.ranges.is_empty() {
+            self.gap_opened_at = None;
+        } else if completed.is_some() || self.gap_opened_at.is_none() {
+            self.gap_opened_at = Some(Instant::now());
+        }
+        completed
+    }
+
+    /// Counts the holes in the completed versions: the one between `next_version` and the
+    /// first range, and the ones between adjacent ranges.
+    fn gap_count(&self) -> usize {
+        let mut expected_version = self.next_version;
+        let mut gap_count = 0;
+        for (start_version, range) in &self.ranges {
+            if *start_version > expected_version {
+                gap_count += 1;
+            }
+            expected_version = range.end_version + 1;
+        }
+        gap_count
+    }
+}
+
 /// Tracks the versioned processing of sequential transactions, ensuring no gaps
 /// occur between them.
 ///
 /// Important: this step assumes ordered transactions. Please use the `OrederByVersionStep` before this step
-/// if the transactions are not ordered.
+/// if the transactions are not ordered, or create the step with `new_gap_tolerant`.
 pub struct VersionTrackerStep<T, S>
 where
     Self: Sized + Send + 'static,
@@ -37,6 +122,8 @@ where
     last_success_batch: Option<TransactionContext<()>>,
     polling_interval_secs: u64,
     processor_status_saver: S,
+    // Only set in the gap-tolerant mode.
+    completed_ranges: Option<CompletedRanges>,
     _marker: PhantomData<T>,
 }
 
@@ -51,10 +138,29 @@ where
             last_success_batch: None,
             processor_status_saver,
             polling_interval_secs,
+            completed_ranges: None,
             _marker: PhantomData,
         }
     }
 
+    /// Creates a step that accepts batches in any order, e.g. from parallel steps. Completed
+    /// ranges are tracked, and the checkpoint only advances to the highest version up to
+    /// which every batch, starting at `starting_version`, has completed.
+    pub fn new_gap_tolerant(
+        processor_status_saver: S,
+        polling_interval_secs: u64,
+        starting_version: u64,
+    ) -> Self {
+        Self {
+            completed_ranges: Some(CompletedRanges {
+                next_version: starting_version,
+                ranges: BTreeMap::new(),
+                gap_opened_at: None,
+            }),
+            ..Self::new(processor_status_saver, polling_interval_secs)
+        }
+    }
+
     async fn save_processor_status(&mut self) -> Result<(), ProcessorError> {
         if let Some(last_success_batch) = self.last_success_batch.as_ref() {
             self.processor_status_saver
@@ -64,6 +170,31 @@ where
             Ok(())
         }
     }
+
+    fn update_gap_metrics(&self) {
+        let labels = StepMetricLabels {
+            step_name: self.name(),
+        };
+        let (gap_count, gap_age_secs) = self
+            .completed_ranges
+            .as_ref()
+            .map(|completed_ranges| {
+                (
+                    completed_ranges.gap_count(),
+                    completed_ranges
+                        .gap_opened_at
+                        .map(|gap_opened_at| gap_opened_at.elapsed().as_secs_f64())
+                        .unwrap_or_default(),
+                )
+            })
+            .unwrap_or_default();
+        VERSION_TRACKER_STEP_GAP_COUNT
+            .get_or_create(&labels)
+            .set(gap_count as i64);
+        VERSION_TRACKER_STEP_GAP_AGE_SECS
+            .get_or_create(&labels)
+            .set(gap_age_secs);
+    }
 }
 
 #[async_trait]
@@ -81,6 +212,22 @@ where
         &mut self,
         current_batch: TransactionContext<T>,
     ) -> Result<Option<TransactionContext<T>>, ProcessorError> {
+        if let Some(completed_ranges) = self.completed_ranges.as_mut() {
+            completed_ranges.insert(current_batch.metadata.clone())?;
+            if let Some(mut completed) = completed_ranges.advance() {
+                // Replace the last batch instead of extending it, which would link every batch's
+                // span to the first one for as long as the processor runs.
+                if let Some(last_success_batch) = self.last_success_batch.as_ref() {
+                    completed.start_version = last_success_batch.metadata.start_version;
+                }
+                self.last_success_batch = Some(TransactionContext {
+                    data: (),
+                    metadata: completed,
+                });
+            }
+            return Ok(Some(current_batch));
+        }
+
         // If there's a gap in version, return an error
         if let Some(last_success_batch) = self.last_success_batch.as_ref() {
             if last_success_batch.metadata.end_version + 1 != current_batch.metadata.start_version {
@@ -124,7 +271,7 @@ where
     }
 
     async fn poll(&mut self) -> Result<Option<Vec<TransactionContext<T>>>, ProcessorError> {
-        // TODO: Add metrics for gap count
+        self.update_gap_metrics();
         self.save_processor_status().await?;
         // Nothing should be returned
         Ok(None)
@@ -141,3 +288,61 @@ where
         format!("VersionTrackerStep: {}", std::any::type_name::<T>())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::file_checkpoint::FileCheckpointStore;
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<()> {
+        TransactionContext {
+            data: (),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_gap_tolerant_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileCheckpointStore::new("test", dir.path().join("checkpoint.json"));
+        let mut step = VersionTrackerStep::<(), _>::new_gap_tolerant(store.clone(), 1, 0);
+        let checkpoint = |step: &VersionTrackerStep<(), FileCheckpointStore>| {
+            step.last_success_batch
+                .as_ref()
+                .map(|batch| batch.metadata.end_version)
+        };
+
+        step.process(batch(20, 29)).await.unwrap().unwrap();
+        step.process(batch(40, 49)).await.unwrap().unwrap();
+        // Versions 0 to 19 and 30 to 39 are missing.
+        assert_eq!(step.completed_ranges.as_ref().unwrap().gap_count(), 2);
+        step.process(batch(10, 19)).await.unwrap().unwrap();
+        step.process(batch(30, 39)).await.unwrap().unwrap();
+        assert_eq!(checkpoint(&step), None);
+        assert_eq!(step.completed_ranges.as_ref().unwrap().ranges.len(), 1);
+        assert_eq!(step.completed_ranges.as_ref().unwrap().gap_count(), 1);
+
+        step.process(batch(0, 9)).await.unwrap().unwrap();
+        assert_eq!(checkpoint(&step), Some(49));
+        assert_eq!(step.completed_ranges.as_ref().unwrap().gap_count(), 0);
+        step.process(batch(50, 59)).await.unwrap().unwrap();
+        assert_eq!(checkpoint(&step), Some(59));
+        let last_success_batch = step.last_success_batch.as_ref().unwrap();
+        assert_eq!(last_success_batch.metadata.start_version, 0);
+
+        step.process(batch(70, 79)).await.unwrap().unwrap();
+        assert_eq!(checkpoint(&step), Some(59));
+        // Overlapping batches are rejected.
+        assert!(step.process(batch(75, 89)).await.is_err());
+        assert!(step.process(batch(5, 9)).await.is_err());
+
+        step.poll().await.unwrap();
+        let saved = store.read().await.unwrap().unwrap();
+        assert_eq!(saved.last_success_version, Some(59));
+    }
+}
@@ -0,0 +1,438 @@
+use crate::{
+    traits::{AsyncRunType, AsyncStep, NamedStep, Processable},
+    types::transaction_context::TransactionContext,
+    utils::errors::ProcessorError,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt};
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::{
+    header::{CONTENT_TYPE, RETRY_AFTER},
+    Client, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::warn;
+use url::Url;
+
+/// HMAC-SHA256 of the request body, as `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+/// Identifies a request across retries, so that receivers can drop duplicates.
+pub const DELIVERY_ID_HEADER: &str = "X-Delivery-Id";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSinkConfig {
+    pub url: Url,
+    /// Batches with more items are split into several requests.
+    #[serde(default = "WebhookSinkConfig::default_max_items_per_request")]
+    pub max_items_per_request: usize,
+    /// If set, every request is signed with this secret. See `SIGNATURE_HEADER`.
+    #[serde(default)]
+    pub hmac_secret: Option<String>,
+    /// Maximum number of requests in flight at once.
+    #[serde(default = "WebhookSinkConfig::default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    #[serde(default = "WebhookSinkConfig::default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Number of retries after the first attempt before the step fails.
+    #[serde(default = "WebhookSinkConfig::default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "WebhookSinkConfig::default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "WebhookSinkConfig::default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// A `Retry-After` header on a retryable response replaces the backoff, even if it's
+    /// longer than `max_backoff_ms`, but is capped at this many seconds.
+    #[serde(default = "WebhookSinkConfig::default_max_retry_after_secs")]
+    pub max_retry_after_secs: u64,
+    /// Non-2xx status codes that count as delivered, e.g. 409 if the endpoint rejects
+    /// duplicates.
+    #[serde(default)]
+    pub accepted_status_codes: Vec<u16>,
+    /// Status codes that are retried, in addition to 408, 429, 5xx and connection errors.
+    /// Any other status code fails the step.
+    #[serde(default)]
+    pub retryable_status_codes: Vec<u16>,
+}
+
+impl WebhookSinkConfig {
+    pub const fn default_max_items_per_request() -> usize {
+        500
+    }
+
+    pub const fn default_max_concurrent_requests() -> usize {
+        4
+    }
+
+    pub const fn default_request_timeout_secs() -> u64 {
+        30
+    }
+
+    pub const fn default_max_retries() -> u32 {
+        5
+    }
+
+    pub const fn default_initial_backoff_ms() -> u64 {
+        500
+    }
+
+    pub const fn default_max_backoff_ms() -> u64 {
+        30_000
+    }
+
+    pub const fn default_max_retry_after_secs() -> u64 {
+        300
+    }
+
+    fn is_retryable(&self, status: StatusCode) -> bool {
+        status == StatusCode::REQUEST_TIMEOUT
+            || status == StatusCode::TOO_MANY_REQUESTS
+            || status.is_server_error()
+            || self.retryable_status_codes.contains(&status.as_u16())
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a, T> {
+    start_version: u64,
+    end_version: u64,
+    items: &'a [T],
+}
+
+struct WebhookRequest {
+    delivery_id: String,
+    body: Vec<u8>,
+    signature: Option<String>,
+}
+
+/// POSTs every batch as JSON to a URL.
+///
+/// A batch is only passed downstream once all of its requests succeed, so a downstream
+/// `VersionTrackerStep` never checkpoints versions that weren't delivered. Failed requests
+/// are retried with exponential backoff; if they still fail, the step returns an error.
+pub struct WebhookSinkStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    config: WebhookSinkConfig,
+    client: Client,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T> WebhookSinkStep<T>
+where
+    Self: Sized + Send + 'static,
+    T: Serialize + Send + 'static,
+{
+    pub fn new(config: WebhookSinkConfig) -> Result<Self, ProcessorError> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.request_timeout_secs))
+            .build()
+            .map_err(|e| ProcessorError::StepInitError {
+                message: format!("Failed to create HTTP client: {e:?}"),
+            })?;
+        Ok(Self {
+            config,
+            client,
+            _marker: std::marker::PhantomData,
+        })
+    }
+
+    fn build_requests(
+        &self,
+        item: &TransactionContext<Vec<T>>,
+    ) -> Result<Vec<WebhookRequest>, ProcessorError> {
+        item.data
+            .chunks(self.config.max_items_per_request.max(1))
+            .enumerate()
+            .map(|(index, items)| {
+                let body = serde_json::to_vec(&WebhookPayload {
+                    start_version: item.metadata.start_version,
+                    end_version: item.metadata.end_version,
+                    items,
+                })
+                .map_err(|e| ProcessorError::ProcessError {
+                    message: format!("Failed to serialize webhook payload: {e:?}"),
+                })?;
+                let signature = self
+                    .config
+                    .hmac_secret
+                    .as_ref()
+                    .map(|secret| sign(secret, &body));
+                Ok(WebhookRequest {
+                    delivery_id: format!(
+                        "{}_{}_{index}",
+                        item.metadata.start_version, item.metadata.end_version
+                    ),
+                    body,
+                    signature,
+                })
+            })
+            .collect()
+    }
+
+    async fn deliver(&self, request: WebhookRequest) -> Result<(), ProcessorError> {
+        let max_backoff = Duration::from_millis(self.config.max_backoff_ms);
+        let mut backoff = Duration::from_millis(self.config.initial_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            let mut builder = self
+                .client
+                .post(self.config.url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .header(DELIVERY_ID_HEADER, &request.delivery_id)
+                .body(request.body.clone());
+            if let Some(signature) = &request.signature {
+                builder = builder.header(SIGNATURE_HEADER, signature);
+            }
+            let (error, retry_after) = match builder.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    if status.is_success()
+                        || self.config.accepted_status_codes.contains(&status.as_u16())
+                    {
+                        return Ok(());
+                    }
+                    if !self.config.is_retryable(status) {
+                        return Err(ProcessorError::ProcessError {
+                            message: format!(
+                                "Webhook delivery {} rejected with status {status}",
+                                request.delivery_id
+                            ),
+                        });
+                    }
+                    (format!("status {status}"), parse_retry_after(&response))
+                },
+                Err(e) => (format!("{e:?}"), None),
+            };
+            if attempt >= self.config.max_retries {
+                return Err(ProcessorError::ProcessError {
+                    message: format!(
+                        "Webhook delivery {} failed after {} attempts: {error}",
+                        request.delivery_id,
+                        attempt + 1
+                    ),
+                });
+            }
+            attempt += 1;
+            let delay = match retry_after {
+                Some(retry_after) => {
+                    retry_after.min(Duration::from_secs(self.config.max_retry_after_secs))
+                },
+                None => backoff,
+            };
+            warn!(
+                delivery_id = %request.delivery_id,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                error = %error,
+                "Webhook delivery failed, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            backoff = (backoff * 2).min(max_backoff);
+        }
+    }
+}
+
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Parses a `Retry-After` header given in seconds.
+fn parse_retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[async_trait]
+impl<T> Processable for WebhookSinkStep<T>
+where
+    T: Serialize + Send + Sync + 'static,
+{
+    type Input = Vec<T>;
+    type Output = Vec<T>;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<T>>,
+    ) -> Result<Option<TransactionContext<Vec<T>>>, ProcessorError> {
+        let requests = self.build_requests(&item)?;
+        futures::stream::iter(requests)
+            .map(|request| self.deliver(request))
+            .buffer_unordered(self.config.max_concurrent_requests.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+        Ok(Some(item))
+    }
+}
+
+impl<T> AsyncStep for WebhookSinkStep<T> where T: Serialize + Send + Sync + 'static {}
+
+impl<T> NamedStep for WebhookSinkStep<T>
+where
+    T: Serialize + Send + 'static,
+{
+    fn name(&self) -> String {
+        format!("WebhookSinkStep: {}", std::any::type_name::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction_context::TransactionMetadata;
+    use axum::{body::Bytes, extract::State, http::HeaderMap, routing::post, Router};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Instant,
+    };
+
+    const SECRET: &str = "secret";
+
+    #[derive(Serialize)]
+    struct TestRow {
+        version: u64,
+    }
+
+    #[derive(Clone, Default)]
+    struct Receiver {
+        // Status codes to return, in order, before accepting requests.
+        failures: Arc<Mutex<Vec<u16>>>,
+        // Sent as `Retry-After` with the failures.
+        retry_after_secs: Option<u64>,
+        received: Arc<Mutex<Vec<serde_json::Value>>>,
+    }
+
+    async fn handle(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> axum::response::Response {
+        use axum::response::IntoResponse;
+
+        let failure = {
+            let mut failures = receiver.failures.lock().unwrap();
+            (!failures.is_empty()).then(|| failures.remove(0))
+        };
+        if let Some(status) = failure {
+            let status = axum::http::StatusCode::from_u16(status).unwrap();
+            return match receiver.retry_after_secs {
+                Some(retry_after_secs) => (
+                    status,
+                    [(RETRY_AFTER.as_str(), retry_after_secs.to_string())],
+                )
+                    .into_response(),
+                None => status.into_response(),
+            };
+        }
+        assert_eq!(headers[SIGNATURE_HEADER], sign(SECRET, &body).as_str());
+        let payload = serde_json::from_slice(&body).unwrap();
+        receiver.received.lock().unwrap().push(payload);
+        axum::http::StatusCode::OK.into_response()
+    }
+
+    async fn start_receiver(receiver: Receiver) -> Url {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let app = Router::new().route("/", post(handle)).with_state(receiver);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{address}/").parse().unwrap()
+    }
+
+    fn step(url: Url) -> WebhookSinkStep<TestRow> {
+        WebhookSinkStep::new(WebhookSinkConfig {
+            url,
+            max_items_per_request: 2,
+            hmac_secret: Some(SECRET.to_string()),
+            max_concurrent_requests: 2,
+            request_timeout_secs: 5,
+            max_retries: 2,
+            initial_backoff_ms: 10,
+            max_backoff_ms: 10,
+            max_retry_after_secs: 1,
+            accepted_status_codes: vec![],
+            retryable_status_codes: vec![],
+        })
+        .unwrap()
+    }
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionContext<Vec<TestRow>> {
+        TransactionContext {
+            data: (start_version..=end_version)
+                .map(|version| TestRow { version })
+                .collect(),
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_webhook_sink_retries_and_splits_batches() {
+        let receiver = Receiver::default();
+        receiver.failures.lock().unwrap().extend([503, 429]);
+        let mut step = step(start_receiver(receiver.clone()).await);
+
+        let output = step.process(batch(0, 4)).await.unwrap().unwrap();
+        assert_eq!(output.metadata.end_version, 4);
+
+        let received = receiver.received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        let mut versions: Vec<u64> = received
+            .iter()
+            .flat_map(|payload| payload["items"].as_array().unwrap().clone())
+            .map(|item| item["version"].as_u64().unwrap())
+            .collect();
+        versions.sort();
+        assert_eq!(versions, vec![0, 1, 2, 3, 4]);
+        assert!(received.iter().all(|payload| payload["end_version"] == 4));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_webhook_sink_honours_retry_after() {
+        let receiver = Receiver {
+            retry_after_secs: Some(60),
+            ..Receiver::default()
+        };
+        receiver.failures.lock().unwrap().push(429);
+        let mut step = step(start_receiver(receiver.clone()).await);
+
+        // Waits longer than `max_backoff_ms`, but only up to `max_retry_after_secs`.
+        let start = Instant::now();
+        step.process(batch(0, 1)).await.unwrap().unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_secs(1));
+        assert!(elapsed < Duration::from_secs(10));
+        assert_eq!(receiver.received.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_webhook_sink_fails_on_rejected_request() {
+        let receiver = Receiver::default();
+        receiver.failures.lock().unwrap().push(400);
+        let mut step = step(start_receiver(receiver.clone()).await);
+
+        // The batch isn't passed downstream, so its versions are never checkpointed.
+        assert!(step.process(batch(0, 1)).await.is_err());
+        assert!(receiver.received.lock().unwrap().is_empty());
+    }
+}
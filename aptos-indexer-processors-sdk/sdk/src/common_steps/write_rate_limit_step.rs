@@ -5,15 +5,19 @@ use crate::{
         errors::ProcessorError,
         step_metrics::{
             StepMetricLabels, WRITE_RATE_LIMIT_STEP_BYTES_WRITTEN,
-            WRITE_RATE_LIMIT_STEP_REMAINING_BYTES,
+            WRITE_RATE_LIMIT_STEP_EFFECTIVE_BYTES_PER_SEC, WRITE_RATE_LIMIT_STEP_REMAINING_BYTES,
         },
     },
 };
 use serde::{Deserialize, Serialize};
 use std::{
+    future::Future,
     marker::PhantomData,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
+use tokio::sync::watch;
+use tracing::warn;
 
 /// Config for WriteRateLimitStep. For example: num_bytes=10,000,000, num_seconds=300
 /// means that the processor can write up to 10 MB per 5 min bucket.
@@ -25,6 +29,121 @@ pub struct WriteRateLimitConfig {
     pub num_seconds: u64,
 }
 
+/// Config for the adaptive mode of WriteRateLimitStep, which adjusts the fill rate with an
+/// AIMD (additive increase, multiplicative decrease) controller. Every adjustment interval,
+/// the rate goes up by `additive_increase_bytes_per_sec` if the DB keeps up, and is
+/// multiplied by `multiplicative_decrease` if it is overloaded.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdaptiveWriteRateLimitConfig {
+    pub min_bytes_per_sec: u64,
+    pub max_bytes_per_sec: u64,
+    pub additive_increase_bytes_per_sec: u64,
+    #[serde(default = "AdaptiveWriteRateLimitConfig::default_multiplicative_decrease")]
+    pub multiplicative_decrease: f64,
+    #[serde(default = "AdaptiveWriteRateLimitConfig::default_adjustment_interval_ms")]
+    pub adjustment_interval_ms: u64,
+}
+
+impl AdaptiveWriteRateLimitConfig {
+    pub const fn default_multiplicative_decrease() -> f64 {
+        0.5
+    }
+
+    pub const fn default_adjustment_interval_ms() -> u64 {
+        1000
+    }
+
+    pub fn validate(&self) -> Result<(), ProcessorError> {
+        if self.min_bytes_per_sec > self.max_bytes_per_sec {
+            return Err(ProcessorError::StepInitError {
+                message: format!(
+                    "min_bytes_per_sec ({}) must not be greater than max_bytes_per_sec ({})",
+                    self.min_bytes_per_sec, self.max_bytes_per_sec
+                ),
+            });
+        }
+        let decrease = self.multiplicative_decrease;
+        if decrease.is_nan() || decrease <= 0.0 || decrease > 1.0 {
+            return Err(ProcessorError::StepInitError {
+                message: format!("multiplicative_decrease must be in (0, 1], got {decrease}"),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Tells the adaptive WriteRateLimitStep whether the DB is overloaded. Implemented for
+/// closures, e.g. to check how full a channel is, and by `LatencyFeedback`.
+pub trait WriteRateLimitFeedback: Send + Sync {
+    fn is_overloaded(&self) -> bool;
+}
+
+impl<F> WriteRateLimitFeedback for F
+where
+    F: Fn() -> bool + Send + Sync,
+{
+    fn is_overloaded(&self) -> bool {
+        self()
+    }
+}
+
+/// Feedback from latency samples, e.g. the processing duration of the DB writing step or the
+/// time spent waiting for a DB connection. Clones share the same samples, so one clone can
+/// be given to the WriteRateLimitStep and another to the DB writing step to record samples,
+/// e.g. with `time`.
+#[derive(Clone)]
+pub struct LatencyFeedback {
+    target_latency: Duration,
+    // Exponentially weighted moving average of the samples, in seconds.
+    average_secs: Arc<Mutex<Option<f64>>>,
+}
+
+impl LatencyFeedback {
+    // Weight of a new sample in the moving average.
+    const SAMPLE_WEIGHT: f64 = 0.2;
+
+    /// The DB counts as overloaded while the average latency is above `target_latency`.
+    pub fn new(target_latency: Duration) -> Self {
+        Self {
+            target_latency,
+            average_secs: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn record(&self, latency: Duration) {
+        let sample = latency.as_secs_f64();
+        let mut average_secs = self.average_secs.lock().unwrap();
+        *average_secs = Some(match *average_secs {
+            Some(average) => average + Self::SAMPLE_WEIGHT * (sample - average),
+            None => sample,
+        });
+    }
+
+    /// Awaits `future` and records how long it took, e.g. the DB write of a batch.
+    pub async fn time<F: Future>(&self, future: F) -> F::Output {
+        let start = Instant::now();
+        let output = future.await;
+        self.record(start.elapsed());
+        output
+    }
+}
+
+impl WriteRateLimitFeedback for LatencyFeedback {
+    fn is_overloaded(&self) -> bool {
+        self.average_secs
+            .lock()
+            .unwrap()
+            .is_some_and(|average| average > self.target_latency.as_secs_f64())
+    }
+}
+
+struct AdaptiveState {
+    config: AdaptiveWriteRateLimitConfig,
+    feedback: Box<dyn WriteRateLimitFeedback>,
+    last_adjusted: Instant,
+}
+
 #[allow(clippy::too_long_first_doc_paragraph)]
 /// This step limits the number of bytes that can be written to the DB per second, based
 /// on a config specifying the number of bytes that can be written in a given number of
@@ -42,6 +161,13 @@ pub struct WriteRateLimitConfig {
 /// derive allocative::Allocative or get_size::GetSize and then using the result from
 /// the related functions, or just implementing it by hand.
 ///
+/// In the adaptive mode (see `new_adaptive`), the fill rate is adjusted over time based on
+/// a `WriteRateLimitFeedback`, and the bucket size follows it so that it always holds
+/// `num_seconds` worth of bytes.
+///
+/// With `with_config_updates`, the limit can be changed while the step runs, e.g. from
+/// `config_reload::ConfigReloader::subscribe`.
+///
 /// This should go before the DB writing step.
 pub struct WriteRateLimitStep<Input>
 where
@@ -59,6 +185,12 @@ where
     fill_rate: f64,
     /// This is the last time the bucket was updated.
     last_updated: Instant,
+    /// This is `num_seconds` from the config, used to resize the bucket in the adaptive mode.
+    num_seconds: f64,
+    /// This is only set in the adaptive mode.
+    adaptive: Option<AdaptiveState>,
+    /// New configs to apply, see `with_config_updates`.
+    config_updates: Option<watch::Receiver<WriteRateLimitConfig>>,
     phantom: PhantomData<Input>,
 }
 
@@ -76,10 +208,95 @@ where
             // Start with a full bucket.
             current_bucket_size: capacity,
             last_updated: Instant::now(),
+            num_seconds: config.num_seconds as f64,
+            adaptive: None,
+            config_updates: None,
             phantom: PhantomData,
         }
     }
 
+    /// Applies the configs sent to `config_updates` before processing the next item. In the
+    /// adaptive mode, a new config resets the fill rate, which then keeps adapting within
+    /// the bounds of the adaptive config.
+    pub fn with_config_updates(
+        mut self,
+        config_updates: watch::Receiver<WriteRateLimitConfig>,
+    ) -> Self {
+        self.config_updates = Some(config_updates);
+        self
+    }
+
+    /// Creates a step whose fill rate starts at the rate from `config` and is then adjusted
+    /// within the bounds from `adaptive_config`, based on `feedback`.
+    pub fn new_adaptive(
+        config: WriteRateLimitConfig,
+        adaptive_config: AdaptiveWriteRateLimitConfig,
+        feedback: impl WriteRateLimitFeedback + 'static,
+    ) -> Result<Self, ProcessorError> {
+        adaptive_config.validate()?;
+        let mut step = Self::new(config);
+        step.adaptive = Some(AdaptiveState {
+            config: adaptive_config,
+            feedback: Box::new(feedback),
+            last_adjusted: Instant::now(),
+        });
+        step.set_fill_rate(step.fill_rate);
+        Ok(step)
+    }
+
+    // Helper function to apply a new config if one was sent to `config_updates`.
+    fn apply_config_updates(&mut self) {
+        let Some(config_updates) = self.config_updates.as_mut() else {
+            return;
+        };
+        if !config_updates.has_changed().unwrap_or(false) {
+            return;
+        }
+        let config = config_updates.borrow_and_update().clone();
+        if config.num_seconds == 0 {
+            warn!(
+                step_name = self.name(),
+                "Ignoring write rate limit config update with num_seconds set to 0"
+            );
+            return;
+        }
+        self.num_seconds = config.num_seconds as f64;
+        self.set_fill_rate(config.num_bytes as f64 / config.num_seconds as f64);
+    }
+
+    /// Sets the fill rate, within the bounds of the adaptive config if there's one.
+    fn set_fill_rate(&mut self, fill_rate: f64) {
+        // Not `f64::clamp`, which panics if the bounds are swapped.
+        let fill_rate = match &self.adaptive {
+            Some(adaptive) => fill_rate
+                .min(adaptive.config.max_bytes_per_sec as f64)
+                .max(adaptive.config.min_bytes_per_sec as f64),
+            None => fill_rate,
+        };
+        self.fill_rate = fill_rate;
+        self.max_bucket_size = fill_rate * self.num_seconds;
+        self.current_bucket_size = self.current_bucket_size.min(self.max_bucket_size);
+    }
+
+    // Helper function to apply the AIMD controller once per adjustment interval.
+    fn adjust_fill_rate(&mut self) {
+        let Some(adaptive) = self.adaptive.as_mut() else {
+            return;
+        };
+        if adaptive.last_adjusted.elapsed()
+            < Duration::from_millis(adaptive.config.adjustment_interval_ms)
+        {
+            return;
+        }
+        adaptive.last_adjusted = Instant::now();
+        let fill_rate = if adaptive.feedback.is_overloaded() {
+            self.fill_rate * adaptive.config.multiplicative_decrease
+        } else {
+            self.fill_rate + adaptive.config.additive_increase_bytes_per_sec as f64
+        };
+        self.set_fill_rate(fill_rate);
+    }
+
     // Helper function to update tokens based on time elapsed since the last update.
     fn update_tokens(&mut self) {
         let now = Instant::now();
@@ -107,6 +324,8 @@ where
         let size_of_item = Sizeable::size_in_bytes(&item.data) as f64;
 
         self.update_tokens();
+        self.apply_config_updates();
+        self.adjust_fill_rate();
 
         let out = if self.current_bucket_size >= size_of_item {
             // We have enough tokens, proceed.
@@ -144,6 +363,12 @@ where
             })
             .inc_by(size_of_item as u64);
 
+        WRITE_RATE_LIMIT_STEP_EFFECTIVE_BYTES_PER_SEC
+            .get_or_create(&StepMetricLabels {
+                step_name: self.name(),
+            })
+            .set(self.fill_rate);
+
         Ok(out)
     }
 }
@@ -168,6 +393,12 @@ pub trait Sizeable {
     fn size_in_bytes(&self) -> u64;
 }
 
+impl<T: Sizeable> Sizeable for Vec<T> {
+    fn size_in_bytes(&self) -> u64 {
+        self.iter().map(Sizeable::size_in_bytes).sum()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -242,6 +473,7 @@ mod tests {
                 start_transaction_timestamp: None,
                 end_transaction_timestamp: None,
                 total_size_in_bytes: item_size as u64,
+                span: Default::default(),
             },
         };
 
@@ -253,6 +485,7 @@ mod tests {
                 start_transaction_timestamp: None,
                 end_transaction_timestamp: None,
                 total_size_in_bytes: item_size as u64,
+                span: Default::default(),
             },
         };
 
@@ -318,4 +551,140 @@ mod tests {
             .get();
         assert!(remaining_bytes < 100);
     }
+
+    #[test]
+    fn test_adaptive_fill_rate() {
+        let overloaded = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let feedback = {
+            let overloaded = overloaded.clone();
+            move || overloaded.load(std::sync::atomic::Ordering::Relaxed)
+        };
+        let mut step = WriteRateLimitStep::<TestData>::new_adaptive(
+            WriteRateLimitConfig {
+                num_bytes: 2000,
+                num_seconds: 2,
+            },
+            AdaptiveWriteRateLimitConfig {
+                min_bytes_per_sec: 200,
+                max_bytes_per_sec: 1500,
+                additive_increase_bytes_per_sec: 400,
+                multiplicative_decrease: 0.5,
+                adjustment_interval_ms: 0,
+            },
+            feedback,
+        )
+        .unwrap();
+        assert_eq!(step.fill_rate, 1000.0);
+
+        // Additive increase, capped at the max.
+        step.adjust_fill_rate();
+        assert_eq!(step.fill_rate, 1400.0);
+        assert_eq!(step.max_bucket_size, 2800.0);
+        step.adjust_fill_rate();
+        assert_eq!(step.fill_rate, 1500.0);
+
+        // Multiplicative decrease, floored at the min.
+        overloaded.store(true, std::sync::atomic::Ordering::Relaxed);
+        step.adjust_fill_rate();
+        assert_eq!(step.fill_rate, 750.0);
+        assert!(step.current_bucket_size <= step.max_bucket_size);
+        for _ in 0..5 {
+            step.adjust_fill_rate();
+        }
+        assert_eq!(step.fill_rate, 200.0);
+    }
+
+    #[test]
+    fn test_config_updates() {
+        let (sender, receiver) = watch::channel(WriteRateLimitConfig {
+            num_bytes: 1000,
+            num_seconds: 1,
+        });
+        let config = receiver.borrow().clone();
+        let mut step = WriteRateLimitStep::<TestData>::new(config).with_config_updates(receiver);
+        step.apply_config_updates();
+        assert_eq!(step.fill_rate, 1000.0);
+
+        sender.send_replace(WriteRateLimitConfig {
+            num_bytes: 3000,
+            num_seconds: 10,
+        });
+        step.apply_config_updates();
+        assert_eq!(step.fill_rate, 300.0);
+        assert_eq!(step.max_bucket_size, 3000.0);
+        assert_eq!(step.current_bucket_size, 1000.0);
+    }
+
+    #[test]
+    fn test_invalid_adaptive_config() {
+        let adaptive_config = AdaptiveWriteRateLimitConfig {
+            min_bytes_per_sec: 2000,
+            max_bytes_per_sec: 1000,
+            additive_increase_bytes_per_sec: 100,
+            multiplicative_decrease: 0.5,
+            adjustment_interval_ms: 0,
+        };
+        let config = WriteRateLimitConfig {
+            num_bytes: 1000,
+            num_seconds: 1,
+        };
+        assert!(WriteRateLimitStep::<TestData>::new_adaptive(
+            config.clone(),
+            adaptive_config.clone(),
+            || false
+        )
+        .is_err());
+        assert!(WriteRateLimitStep::<TestData>::new_adaptive(
+            config,
+            AdaptiveWriteRateLimitConfig {
+                max_bytes_per_sec: 3000,
+                multiplicative_decrease: 1.5,
+                ..adaptive_config
+            },
+            || false
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_config_updates_with_zero_seconds_are_ignored() {
+        let (sender, receiver) = watch::channel(WriteRateLimitConfig {
+            num_bytes: 1000,
+            num_seconds: 1,
+        });
+        let config = receiver.borrow().clone();
+        let mut step = WriteRateLimitStep::<TestData>::new(config).with_config_updates(receiver);
+        sender.send_replace(WriteRateLimitConfig {
+            num_bytes: 1000,
+            num_seconds: 0,
+        });
+        step.apply_config_updates();
+        assert_eq!(step.fill_rate, 1000.0);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_latency_feedback_time() {
+        let feedback = LatencyFeedback::new(Duration::from_millis(100));
+        let output = feedback
+            .time(async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                1
+            })
+            .await;
+        assert_eq!(output, 1);
+        assert!(feedback.is_overloaded());
+    }
+
+    #[test]
+    fn test_latency_feedback() {
+        let feedback = LatencyFeedback::new(Duration::from_millis(100));
+        assert!(!feedback.is_overloaded());
+        feedback.clone().record(Duration::from_millis(500));
+        assert!(feedback.is_overloaded());
+        for _ in 0..20 {
+            feedback.record(Duration::from_millis(10));
+        }
+        assert!(!feedback.is_overloaded());
+    }
 }
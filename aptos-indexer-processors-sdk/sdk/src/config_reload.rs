@@ -0,0 +1,385 @@
+//! Hot reloading of config values without restarting the processor.
+//!
+//! Components opt in by subscribing to the key path of their settings with
+//! `ConfigReloader::subscribe`, e.g. `server_config.write_rate_limit_config`, and applying
+//! the values they receive. On `SIGHUP`, or when the config file changes if
+//! `watch_interval_secs` is set, the config is loaded again and validated. If only subscribed
+//! keys changed, the new values are pushed to their subscribers. Otherwise the reload is
+//! rejected as a whole and the processor keeps running with the old config, since the other
+//! settings only take effect on restart.
+//!
+//! The server framework subscribes to `logging_config.filter`, `logging_config.levels` and
+//! `logging_config.sampling`. The basic processors also subscribe to the
+//! `progress_health_config`, `chain_lag_health_config` and `stream_health_config` sections of
+//! their `server_config`, if they are set.
+
+use anyhow::{anyhow, bail, Result};
+use once_cell::sync::OnceCell;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_yaml::Value;
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::sync::watch;
+use tracing::{error, info};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigReloadConfig {
+    /// If set, the config file is checked for changes this often. The config is always
+    /// reloaded on `SIGHUP`.
+    #[serde(default)]
+    pub watch_interval_secs: Option<u64>,
+}
+
+type LoadConfig = Box<dyn Fn() -> Result<Value> + Send + Sync>;
+// Deserializes the new value of a subscribed key, and returns a function that sends it.
+type PrepareUpdate = Box<dyn Fn(&Value) -> Result<Box<dyn FnOnce() + Send>> + Send + Sync>;
+
+struct Subscription {
+    key_path: Vec<String>,
+    prepare_update: PrepareUpdate,
+}
+
+struct ReloaderState {
+    config: Value,
+    subscriptions: Vec<Subscription>,
+}
+
+/// Reloads the config and pushes the changed values to the subscribers, see the module docs.
+pub struct ConfigReloader {
+    config_path: PathBuf,
+    load_config: LoadConfig,
+    state: Mutex<ReloaderState>,
+}
+
+static CONFIG_RELOADER: OnceCell<ConfigReloader> = OnceCell::new();
+
+/// The reloader installed by the server framework when `config_reload_config` is set.
+pub fn config_reloader() -> Option<&'static ConfigReloader> {
+    CONFIG_RELOADER.get()
+}
+
+impl ConfigReloader {
+    /// `load_config` loads and validates the config from `config_path`, and is called once
+    /// here for the initial config.
+    pub fn new(
+        config_path: PathBuf,
+        load_config: impl Fn() -> Result<Value> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let config = load_config()?;
+        Ok(Self {
+            config_path,
+            load_config: Box::new(load_config),
+            state: Mutex::new(ReloaderState {
+                config,
+                subscriptions: vec![],
+            }),
+        })
+    }
+
+    /// Makes this the reloader returned by `config_reloader`.
+    pub fn install(self) -> Result<&'static ConfigReloader> {
+        CONFIG_RELOADER
+            .set(self)
+            .map_err(|_| anyhow!("A config reloader is already installed"))?;
+        Ok(CONFIG_RELOADER.get().unwrap())
+    }
+
+    /// The current config, including the reloaded values.
+    pub fn config(&self) -> Value {
+        self.state.lock().unwrap().config.clone()
+    }
+
+    /// Marks the value at `key_path`, e.g. `server_config.progress_health_config`, as
+    /// reloadable and returns a receiver of its current and reloaded values. A missing key
+    /// deserializes from null, so subscribe to an `Option` for optional sections.
+    pub fn subscribe<T>(&self, key_path: &str) -> Result<watch::Receiver<T>>
+    where
+        T: DeserializeOwned + Send + Sync + 'static,
+    {
+        let key_path: Vec<String> = key_path.split('.').map(str::to_string).collect();
+        let mut state = self.state.lock().unwrap();
+        let initial = deserialize_at::<T>(&state.config, &key_path)?;
+        let (sender, receiver) = watch::channel(initial);
+        let sender = Arc::new(sender);
+        let prepare_update: PrepareUpdate = {
+            let key_path = key_path.clone();
+            Box::new(move |config| {
+                let value = deserialize_at::<T>(config, &key_path)?;
+                let sender = sender.clone();
+                Ok(Box::new(move || {
+                    sender.send_replace(value);
+                }))
+            })
+        };
+        state.subscriptions.push(Subscription {
+            key_path,
+            prepare_update,
+        });
+        Ok(receiver)
+    }
+
+    /// Loads the config again and pushes the changed values to their subscribers. Returns the
+    /// changed key paths, or an error without applying anything if the new config is invalid
+    /// or a key nobody subscribed to changed.
+    pub fn reload(&self) -> Result<Vec<String>> {
+        let config = (self.load_config)()?;
+        let mut state = self.state.lock().unwrap();
+        let mut changed_key_paths = vec![];
+        changed_keys(&state.config, &config, &mut vec![], &mut changed_key_paths);
+
+        let is_subscribed = |key_path: &Vec<String>| {
+            state
+                .subscriptions
+                .iter()
+                .any(|subscription| key_path.starts_with(&subscription.key_path))
+        };
+        let not_reloadable: Vec<String> = changed_key_paths
+            .iter()
+            .filter(|key_path| !is_subscribed(key_path))
+            .map(|key_path| format!("`{}`", key_path.join(".")))
+            .collect();
+        if !not_reloadable.is_empty() {
+            bail!(
+                "{} can't be changed without a restart",
+                not_reloadable.join(", ")
+            );
+        }
+
+        let updates = state
+            .subscriptions
+            .iter()
+            .filter(|subscription| {
+                changed_key_paths
+                    .iter()
+                    .any(|key_path| key_path.starts_with(&subscription.key_path))
+            })
+            .map(|subscription| (subscription.prepare_update)(&config))
+            .collect::<Result<Vec<_>>>()?;
+        updates.into_iter().for_each(|update| update());
+        state.config = config;
+        Ok(changed_key_paths
+            .into_iter()
+            .map(|key_path| key_path.join("."))
+            .collect())
+    }
+
+    /// Reloads the config on `SIGHUP`, and whenever the modification time of the config file
+    /// changes if `watch_interval` is set. Runs forever.
+    pub async fn watch(&self, watch_interval: Option<Duration>) {
+        #[cfg(unix)]
+        let mut sighup =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+        let mut interval = watch_interval.map(tokio::time::interval);
+        let mut last_modified = self.modified();
+        loop {
+            #[cfg(unix)]
+            let sighup_received = async {
+                match sighup.as_mut() {
+                    Some(sighup) => sighup.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            #[cfg(not(unix))]
+            let sighup_received = std::future::pending::<Option<()>>();
+            let file_checked = async {
+                match interval.as_mut() {
+                    Some(interval) => interval.tick().await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                _ = sighup_received => {
+                    info!("Received SIGHUP, reloading config");
+                },
+                _ = file_checked => {
+                    let modified = self.modified();
+                    if modified == last_modified {
+                        continue;
+                    }
+                    last_modified = modified;
+                    info!(config_path = ?self.config_path, "Config file changed, reloading config");
+                },
+            }
+            match self.reload() {
+                Ok(changed_key_paths) => {
+                    info!(?changed_key_paths, "Reloaded config");
+                },
+                Err(e) => {
+                    error!(
+                        error = format!("{e:#}"),
+                        "Rejected config reload, keeping the current config"
+                    );
+                },
+            }
+        }
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        std::fs::metadata(&self.config_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+}
+
+fn deserialize_at<T: DeserializeOwned>(config: &Value, key_path: &[String]) -> Result<T> {
+    let mut value = config;
+    for key in key_path {
+        value = match value.get(key.as_str()) {
+            Some(value) => value,
+            None => &Value::Null,
+        };
+    }
+    serde_path_to_error::deserialize(value.clone()).map_err(|e| {
+        // The path is "." when the value itself is invalid.
+        let path = match e.path().to_string().as_str() {
+            "." => key_path.join("."),
+            path => format!("{}.{}", key_path.join("."), path),
+        };
+        anyhow!("Invalid config at `{}`: {}", path, e.inner())
+    })
+}
+
+// Collects the key paths of the values that differ between `old` and `new`. Maps are compared
+// key by key, with a missing map being empty, any other values as a whole.
+fn changed_keys(
+    old: &Value,
+    new: &Value,
+    key_path: &mut Vec<String>,
+    changed_key_paths: &mut Vec<Vec<String>>,
+) {
+    match (old, new) {
+        (Value::Mapping(old), Value::Mapping(new)) => {
+            let keys: Vec<&Value> = old
+                .iter()
+                .map(|(key, _)| key)
+                .chain(
+                    new.iter()
+                        .map(|(key, _)| key)
+                        .filter(|key| !old.contains_key(key)),
+                )
+                .collect();
+            for key in keys {
+                let old_value = old.get(key).unwrap_or(&Value::Null);
+                let new_value = new.get(key).unwrap_or(&Value::Null);
+                key_path.push(match key {
+                    Value::String(key) => key.clone(),
+                    key => format!("{key:?}"),
+                });
+                changed_keys(old_value, new_value, key_path, changed_key_paths);
+                key_path.pop();
+            }
+        },
+        // A section that is added or removed changes each of its keys.
+        (Value::Null, Value::Mapping(_)) => changed_keys(
+            &Value::Mapping(Default::default()),
+            new,
+            key_path,
+            changed_key_paths,
+        ),
+        (Value::Mapping(_), Value::Null) => changed_keys(
+            old,
+            &Value::Mapping(Default::default()),
+            key_path,
+            changed_key_paths,
+        ),
+        (old, new) if old != new => changed_key_paths.push(key_path.clone()),
+        _ => {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::ProgressHealthConfig;
+
+    fn reloader(config_path: PathBuf) -> ConfigReloader {
+        let path = config_path.clone();
+        ConfigReloader::new(config_path, move || {
+            Ok(serde_yaml::from_str(&std::fs::read_to_string(&path)?)?)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        std::fs::write(
+            &config_path,
+            "health_check_port: 8085\nserver_config:\n  progress_health_config:\n    no_progress_threshold_secs: 30\n",
+        )
+        .unwrap();
+        let reloader = reloader(config_path.clone());
+        let mut progress_health_config = reloader
+            .subscribe::<ProgressHealthConfig>("server_config.progress_health_config")
+            .unwrap();
+        let missing = reloader
+            .subscribe::<Option<u64>>("server_config.missing")
+            .unwrap();
+        let mut filter = reloader
+            .subscribe::<Option<String>>("logging_config.filter")
+            .unwrap();
+        assert_eq!(
+            progress_health_config
+                .borrow_and_update()
+                .no_progress_threshold_secs,
+            30
+        );
+        assert!(missing.borrow().is_none());
+
+        std::fs::write(
+            &config_path,
+            "health_check_port: 8085\nserver_config:\n  progress_health_config:\n    no_progress_threshold_secs: 90\n",
+        )
+        .unwrap();
+        assert_eq!(reloader.reload().unwrap(), vec![
+            "server_config.progress_health_config.no_progress_threshold_secs"
+        ]);
+        assert!(progress_health_config.has_changed().unwrap());
+        assert_eq!(
+            progress_health_config
+                .borrow_and_update()
+                .no_progress_threshold_secs,
+            90
+        );
+
+        // Changing a key nobody subscribed to rejects the whole reload.
+        std::fs::write(
+            &config_path,
+            "health_check_port: 8086\nserver_config:\n  progress_health_config:\n    no_progress_threshold_secs: 120\n",
+        )
+        .unwrap();
+        let error = reloader.reload().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`health_check_port` can't be changed without a restart"
+        );
+        assert!(!progress_health_config.has_changed().unwrap());
+
+        // So does an invalid value.
+        std::fs::write(
+            &config_path,
+            "health_check_port: 8085\nserver_config:\n  progress_health_config:\n    no_progress_threshold_secs: soon\n",
+        )
+        .unwrap();
+        let error = reloader.reload().unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("server_config.progress_health_config.no_progress_threshold_secs"));
+        assert!(!progress_health_config.has_changed().unwrap());
+
+        // Adding a section counts as changing each of its keys.
+        std::fs::write(
+            &config_path,
+            "health_check_port: 8085\nserver_config:\n  progress_health_config:\n    no_progress_threshold_secs: 90\nlogging_config:\n  filter: debug\n",
+        )
+        .unwrap();
+        assert_eq!(reloader.reload().unwrap(), vec!["logging_config.filter"]);
+        assert_eq!(filter.borrow_and_update().as_deref(), Some("debug"));
+    }
+}
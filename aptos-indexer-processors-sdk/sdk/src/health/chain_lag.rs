@@ -0,0 +1,310 @@
+//! Chain lag health checking for processors.
+
+use super::core::HealthCheck;
+use crate::utils::{
+    latest_seen_timestamp::LatestSeenTimestamp,
+    pipeline_status::{pause_controller, PauseController},
+};
+use async_trait::async_trait;
+use chrono::{NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicI64, Ordering};
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Configuration for chain lag health checking.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChainLagHealthConfig {
+    /// Lag in seconds above which a warning is logged and reported, but the processor is still
+    /// considered healthy.
+    #[serde(default = "default_warn_lag_secs")]
+    pub warn_lag_secs: u64,
+    /// Lag in seconds above which the processor is considered unhealthy.
+    #[serde(default = "default_fail_lag_secs")]
+    pub fail_lag_secs: u64,
+}
+
+pub const fn default_warn_lag_secs() -> u64 {
+    60
+}
+
+pub const fn default_fail_lag_secs() -> u64 {
+    600
+}
+
+impl Default for ChainLagHealthConfig {
+    fn default() -> Self {
+        Self {
+            warn_lag_secs: default_warn_lag_secs(),
+            fail_lag_secs: default_fail_lag_secs(),
+        }
+    }
+}
+
+/// A trait for providing the timestamp of the last transaction the processor has processed.
+///
+/// Implement this trait to provide a custom backend for chain lag health checking.
+/// The SDK implements it for `PostgresProgressStatusProvider`, `SqliteProgressStatusProvider`
+/// and `FileCheckpointStore`.
+#[async_trait]
+pub trait TransactionTimestampProvider: Send + Sync {
+    /// Get the timestamp of the last processed transaction.
+    /// Returns `None` if the processor hasn't written status yet (e.g., during startup).
+    async fn get_last_transaction_timestamp(&self) -> Result<Option<NaiveDateTime>, String>;
+}
+
+/// A health check that verifies the processor isn't too far behind the chain.
+///
+/// Unlike `ProgressHealthChecker`, which only checks that the status was updated recently,
+/// this compares the timestamp of the last processed transaction against the current time,
+/// or against the newest transaction seen on the stream if `with_latest_seen_timestamp` is
+/// used. The latter measures how far processing is behind the stream, even when the stream
+/// itself is behind the chain.
+///
+/// The check passes while the pipeline is paused, as the lag keeps growing by design, and for
+/// `fail_lag_secs` after it's resumed, to give the processor time to catch up.
+pub struct ChainLagHealthChecker {
+    processor_name: String,
+    timestamp_provider: Box<dyn TransactionTimestampProvider>,
+    latest_seen_timestamp: Option<LatestSeenTimestamp>,
+    config: ChainLagHealthConfig,
+    /// New configs to apply, see `with_config_updates`.
+    config_updates: Option<watch::Receiver<ChainLagHealthConfig>>,
+    // Lag measured by the last call to `is_healthy`, reported by `status`.
+    last_lag_secs: AtomicI64,
+    pause_controller: &'static PauseController,
+}
+
+impl ChainLagHealthChecker {
+    pub fn new(
+        processor_name: String,
+        timestamp_provider: Box<dyn TransactionTimestampProvider>,
+        config: ChainLagHealthConfig,
+    ) -> Self {
+        Self {
+            processor_name,
+            timestamp_provider,
+            latest_seen_timestamp: None,
+            config,
+            config_updates: None,
+            last_lag_secs: AtomicI64::new(-1),
+            pause_controller: pause_controller(),
+        }
+    }
+
+    /// Measures lag against the newest transaction seen on the stream instead of the current
+    /// time. Until the stream has delivered a transaction, the processor is considered healthy.
+    pub fn with_latest_seen_timestamp(
+        mut self,
+        latest_seen_timestamp: LatestSeenTimestamp,
+    ) -> Self {
+        self.latest_seen_timestamp = Some(latest_seen_timestamp);
+        self
+    }
+
+    /// Uses the latest config sent to `config_updates`, e.g. from
+    /// `config_reload::ConfigReloader::subscribe`, instead of the initial one.
+    pub fn with_config_updates(
+        mut self,
+        config_updates: watch::Receiver<ChainLagHealthConfig>,
+    ) -> Self {
+        self.config_updates = Some(config_updates);
+        self
+    }
+
+    fn config(&self) -> ChainLagHealthConfig {
+        match &self.config_updates {
+            Some(config_updates) => config_updates.borrow().clone(),
+            None => self.config.clone(),
+        }
+    }
+
+    /// Returns the lag in seconds, or `None` if it can't be measured yet.
+    async fn lag_secs(&self) -> Result<Option<i64>, String> {
+        let Some(last_transaction_timestamp) = self
+            .timestamp_provider
+            .get_last_transaction_timestamp()
+            .await?
+        else {
+            return Ok(None);
+        };
+        let reference = match &self.latest_seen_timestamp {
+            Some(latest_seen_timestamp) => match latest_seen_timestamp.get() {
+                Some(timestamp) => timestamp,
+                None => return Ok(None),
+            },
+            None => Utc::now().naive_utc(),
+        };
+        Ok(Some(
+            (reference - last_transaction_timestamp)
+                .num_seconds()
+                .max(0),
+        ))
+    }
+}
+
+#[async_trait]
+impl HealthCheck for ChainLagHealthChecker {
+    fn name(&self) -> &str {
+        "ChainLag"
+    }
+
+    async fn is_healthy(&self) -> Result<(), String> {
+        let Some(lag_secs) = self.lag_secs().await? else {
+            // Nothing has been processed yet. This is okay during startup.
+            self.last_lag_secs.store(-1, Ordering::Relaxed);
+            return Ok(());
+        };
+        self.last_lag_secs.store(lag_secs, Ordering::Relaxed);
+        let config = self.config();
+        if self
+            .pause_controller
+            .paused_within(std::time::Duration::from_secs(config.fail_lag_secs))
+        {
+            return Ok(());
+        }
+
+        let fail_lag_secs = config.fail_lag_secs as i64;
+        let warn_lag_secs = config.warn_lag_secs as i64;
+        if lag_secs > fail_lag_secs {
+            warn!(
+                processor = %self.processor_name,
+                lag_secs,
+                fail_lag_secs,
+                "Processor is too far behind the chain"
+            );
+            Err(format!(
+                "Lagging {} seconds behind (threshold: {} seconds)",
+                lag_secs, fail_lag_secs
+            ))
+        } else {
+            if lag_secs > warn_lag_secs {
+                warn!(
+                    processor = %self.processor_name,
+                    lag_secs,
+                    warn_lag_secs,
+                    "Processor is falling behind the chain"
+                );
+            }
+            Ok(())
+        }
+    }
+
+    async fn status(&self) -> Option<String> {
+        if self.pause_controller.is_paused() {
+            return Some("Paused".to_string());
+        }
+        let lag_secs = self.last_lag_secs.load(Ordering::Relaxed);
+        if lag_secs < 0 {
+            return None;
+        }
+        let warn_lag_secs = self.config().warn_lag_secs;
+        if lag_secs > warn_lag_secs as i64 {
+            Some(format!(
+                "Lagging {} seconds behind (warning threshold: {} seconds)",
+                lag_secs, warn_lag_secs
+            ))
+        } else {
+            Some(format!("Lagging {} seconds behind", lag_secs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    struct FixedTimestamp(Option<NaiveDateTime>);
+
+    #[async_trait]
+    impl TransactionTimestampProvider for FixedTimestamp {
+        async fn get_last_transaction_timestamp(&self) -> Result<Option<NaiveDateTime>, String> {
+            Ok(self.0)
+        }
+    }
+
+    fn checker(lag: Option<Duration>) -> ChainLagHealthChecker {
+        checker_with_config(lag, ChainLagHealthConfig {
+            warn_lag_secs: 60,
+            fail_lag_secs: 600,
+        })
+    }
+
+    fn checker_with_config(
+        lag: Option<Duration>,
+        config: ChainLagHealthConfig,
+    ) -> ChainLagHealthChecker {
+        ChainLagHealthChecker::new(
+            "test".to_string(),
+            Box::new(FixedTimestamp(lag.map(|lag| Utc::now().naive_utc() - lag))),
+            config,
+        )
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_chain_lag_thresholds() {
+        let check = checker(None);
+        assert!(check.is_healthy().await.is_ok());
+        assert_eq!(check.status().await, None);
+
+        let check = checker(Some(Duration::seconds(5)));
+        assert!(check.is_healthy().await.is_ok());
+        assert!(!check.status().await.unwrap().contains("warning"));
+
+        let check = checker(Some(Duration::seconds(120)));
+        assert!(check.is_healthy().await.is_ok());
+        assert!(check.status().await.unwrap().contains("warning"));
+
+        let check = checker(Some(Duration::seconds(1200)));
+        assert!(check.is_healthy().await.is_err());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_chain_lag_against_latest_seen() {
+        let latest_seen_timestamp = LatestSeenTimestamp::new();
+        // The processor is an hour behind the current time, but the stream is too.
+        let check = checker(Some(Duration::seconds(3600)))
+            .with_latest_seen_timestamp(latest_seen_timestamp.clone());
+        assert!(check.is_healthy().await.is_ok());
+        assert_eq!(check.status().await, None);
+
+        latest_seen_timestamp.record(Utc::now().naive_utc() - Duration::seconds(3590));
+        assert!(check.is_healthy().await.is_ok());
+        assert!((9..=11).contains(&check.last_lag_secs.load(Ordering::Relaxed)));
+
+        latest_seen_timestamp.record(Utc::now().naive_utc());
+        assert!(check.is_healthy().await.is_err());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_chain_lag_passes_while_paused() {
+        let mut check = checker(Some(Duration::seconds(1200)));
+        // A controller of its own, so that other tests don't see the pause.
+        check.pause_controller = Box::leak(Box::new(PauseController::new()));
+        assert!(check.is_healthy().await.is_err());
+
+        check.pause_controller.pause();
+        assert!(check.is_healthy().await.is_ok());
+        assert_eq!(check.status().await.as_deref(), Some("Paused"));
+
+        // Right after a resume, the processor gets `fail_lag_secs` to catch up.
+        check.pause_controller.resume();
+        assert!(check.is_healthy().await.is_ok());
+        assert!(check.status().await.unwrap().contains("Lagging"));
+
+        // Once the grace period is over, the lag counts again.
+        let mut check = checker_with_config(Some(Duration::seconds(1200)), ChainLagHealthConfig {
+            warn_lag_secs: 0,
+            fail_lag_secs: 0,
+        });
+        check.pause_controller = Box::leak(Box::new(PauseController::new()));
+        check.pause_controller.pause();
+        assert!(check.is_healthy().await.is_ok());
+        check.pause_controller.resume();
+        assert!(check.is_healthy().await.is_err());
+    }
+}
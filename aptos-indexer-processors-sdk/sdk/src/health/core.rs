@@ -2,8 +2,8 @@ use async_trait::async_trait;
 
 /// A trait for implementing custom health checks.
 ///
-/// Implementations can be passed to `register_probes_and_metrics_handler` to add
-/// custom health checks to the `/healthz` endpoint.
+/// Implementations can be passed to `register_probes_and_metrics_handler`, as liveness
+/// checks for `/livez` and `/healthz` or as readiness checks for `/readyz`.
 #[async_trait]
 pub trait HealthCheck: Send + Sync {
     /// Returns the name of this health check (used in error messages).
@@ -12,4 +12,10 @@ pub trait HealthCheck: Send + Sync {
     /// Check if this component is healthy.
     /// Returns `Ok(())` if healthy, or `Err(reason)` if not healthy.
     async fn is_healthy(&self) -> Result<(), String>;
+
+    /// Optional description of the component's current state, reported by `/healthz`
+    /// alongside the check result, e.g. whether this replica is the leader.
+    async fn status(&self) -> Option<String> {
+        None
+    }
 }
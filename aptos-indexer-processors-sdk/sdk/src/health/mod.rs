@@ -1,11 +1,22 @@
 //! Health checking utilities for processors.
 
+pub mod chain_lag;
 pub mod core;
 pub mod progress;
+pub mod readiness;
+pub mod report;
+pub mod stream;
 
 // Re-export commonly used types.
+pub use chain_lag::{
+    default_fail_lag_secs, default_warn_lag_secs, ChainLagHealthChecker, ChainLagHealthConfig,
+    TransactionTimestampProvider,
+};
 pub use core::HealthCheck;
 pub use progress::{
     default_no_progress_threshold_secs, ProgressHealthChecker, ProgressHealthConfig,
     ProgressStatusProvider,
 };
+pub use readiness::ReadinessGate;
+pub use report::{run_health_checks, HealthChecks, HealthReport};
+pub use stream::{StreamHealthChecker, StreamHealthConfig};
@@ -1,9 +1,12 @@
 //! Progress health checking for processors.
 
 use super::core::HealthCheck;
+use crate::utils::pipeline_status::{pause_controller, PauseController};
 use async_trait::async_trait;
 use chrono::{NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::watch;
 use tracing::warn;
 
 /// Configuration for progress health checking.
@@ -41,10 +44,14 @@ pub trait ProgressStatusProvider: Send + Sync {
 /// A health check that verifies the processor is making forward progress.
 ///
 /// This is generic over the status provider, allowing different backends (postgres, etc.).
+/// The check passes while the pipeline is paused and for the threshold after it resumes.
 pub struct ProgressHealthChecker {
     processor_name: String,
     status_provider: Box<dyn ProgressStatusProvider>,
     no_progress_threshold_secs: u64,
+    /// New configs to apply, see `with_config_updates`.
+    config_updates: Option<watch::Receiver<ProgressHealthConfig>>,
+    pause_controller: &'static PauseController,
 }
 
 impl ProgressHealthChecker {
@@ -57,6 +64,25 @@ impl ProgressHealthChecker {
             processor_name,
             status_provider,
             no_progress_threshold_secs: config.no_progress_threshold_secs,
+            config_updates: None,
+            pause_controller: pause_controller(),
+        }
+    }
+
+    /// Uses the latest config sent to `config_updates`, e.g. from
+    /// `config_reload::ConfigReloader::subscribe`, instead of the initial one.
+    pub fn with_config_updates(
+        mut self,
+        config_updates: watch::Receiver<ProgressHealthConfig>,
+    ) -> Self {
+        self.config_updates = Some(config_updates);
+        self
+    }
+
+    fn no_progress_threshold_secs(&self) -> u64 {
+        match &self.config_updates {
+            Some(config_updates) => config_updates.borrow().no_progress_threshold_secs,
+            None => self.no_progress_threshold_secs,
         }
     }
 }
@@ -68,13 +94,19 @@ impl HealthCheck for ProgressHealthChecker {
     }
 
     async fn is_healthy(&self) -> Result<(), String> {
+        if self
+            .pause_controller
+            .paused_within(Duration::from_secs(self.no_progress_threshold_secs()))
+        {
+            return Ok(());
+        }
         let last_updated = self.status_provider.get_last_updated().await?;
 
         match last_updated {
             Some(last_updated) => {
                 let now = Utc::now().naive_utc();
                 let seconds_since_update = (now - last_updated).num_seconds();
-                let timeout = self.no_progress_threshold_secs as i64;
+                let timeout = self.no_progress_threshold_secs() as i64;
 
                 if seconds_since_update > timeout {
                     warn!(
@@ -98,4 +130,10 @@ impl HealthCheck for ProgressHealthChecker {
             },
         }
     }
+
+    async fn status(&self) -> Option<String> {
+        self.pause_controller
+            .is_paused()
+            .then(|| "Paused".to_string())
+    }
 }
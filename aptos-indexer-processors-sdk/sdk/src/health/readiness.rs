@@ -0,0 +1,49 @@
+//! Readiness gating for processors.
+
+use super::core::HealthCheck;
+use async_trait::async_trait;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A health check that fails until `mark_ready` is called, e.g. once migrations have run
+/// and the chain id has been verified. Clones share the same state, so one clone can be
+/// registered as a readiness check and another handed to the code doing the startup work.
+#[derive(Clone)]
+pub struct ReadinessGate {
+    name: String,
+    ready: Arc<AtomicBool>,
+}
+
+impl ReadinessGate {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ready: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub fn mark_ready(&self) {
+        self.ready.store(true, Ordering::Release);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+}
+
+#[async_trait]
+impl HealthCheck for ReadinessGate {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn is_healthy(&self) -> Result<(), String> {
+        if self.is_ready() {
+            Ok(())
+        } else {
+            Err("Still starting up".to_string())
+        }
+    }
+}
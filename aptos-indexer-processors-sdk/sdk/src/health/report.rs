@@ -0,0 +1,158 @@
+//! Running health checks and reporting their results.
+
+use super::core::HealthCheck;
+use serde::Serialize;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The health checks served by the probes. Liveness checks back `/livez` and `/healthz`,
+/// readiness checks back `/readyz`.
+#[derive(Clone)]
+pub struct HealthChecks {
+    pub liveness: Vec<Arc<dyn HealthCheck>>,
+    pub readiness: Vec<Arc<dyn HealthCheck>>,
+    /// A check that takes longer than this fails.
+    pub check_timeout: Duration,
+}
+
+impl Default for HealthChecks {
+    fn default() -> Self {
+        Self {
+            liveness: vec![],
+            readiness: vec![],
+            check_timeout: DEFAULT_HEALTH_CHECK_TIMEOUT,
+        }
+    }
+}
+
+/// Checks passed without a split are liveness checks, which is how `/healthz` has always
+/// used them.
+impl From<Vec<Arc<dyn HealthCheck>>> for HealthChecks {
+    fn from(liveness: Vec<Arc<dyn HealthCheck>>) -> Self {
+        Self {
+            liveness,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CheckReport {
+    pub name: String,
+    pub status: CheckStatus,
+    pub latency_ms: u64,
+    /// Why the check failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// See `HealthCheck::status`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HealthReport {
+    pub status: CheckStatus,
+    pub checks: Vec<CheckReport>,
+}
+
+impl HealthReport {
+    pub fn is_healthy(&self) -> bool {
+        self.status == CheckStatus::Pass
+    }
+}
+
+/// Runs `checks` concurrently. Each check, including its status, has `timeout` to finish.
+pub async fn run_health_checks(checks: &[Arc<dyn HealthCheck>], timeout: Duration) -> HealthReport {
+    let checks = futures::future::join_all(checks.iter().map(|check| async move {
+        let start = Instant::now();
+        let result = tokio::time::timeout(timeout, async {
+            (check.is_healthy().await, check.status().await)
+        })
+        .await;
+        let latency_ms = start.elapsed().as_millis() as u64;
+        let (reason, details) = match result {
+            Ok((Ok(()), details)) => (None, details),
+            Ok((Err(reason), details)) => (Some(reason), details),
+            Err(_) => (Some(format!("Timed out after {timeout:?}")), None),
+        };
+        CheckReport {
+            name: check.name().to_string(),
+            status: if reason.is_none() {
+                CheckStatus::Pass
+            } else {
+                CheckStatus::Fail
+            },
+            latency_ms,
+            reason,
+            details,
+        }
+    }))
+    .await;
+    let status = if checks.iter().all(|check| check.status == CheckStatus::Pass) {
+        CheckStatus::Pass
+    } else {
+        CheckStatus::Fail
+    };
+    HealthReport { status, checks }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::health::ReadinessGate;
+    use async_trait::async_trait;
+
+    struct SlowCheck;
+
+    #[async_trait]
+    impl HealthCheck for SlowCheck {
+        fn name(&self) -> &str {
+            "Slow"
+        }
+
+        async fn is_healthy(&self) -> Result<(), String> {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_run_health_checks() {
+        let gate = ReadinessGate::new("Startup");
+        let checks: Vec<Arc<dyn HealthCheck>> = vec![Arc::new(gate.clone()), Arc::new(SlowCheck)];
+
+        let start = Instant::now();
+        let report = run_health_checks(&checks, Duration::from_millis(50)).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(!report.is_healthy());
+        assert_eq!(
+            report.checks[0].reason.as_deref(),
+            Some("Still starting up")
+        );
+        assert!(report.checks[1]
+            .reason
+            .as_ref()
+            .unwrap()
+            .contains("Timed out"));
+
+        gate.mark_ready();
+        let report = run_health_checks(&checks[..1], Duration::from_millis(50)).await;
+        assert!(report.is_healthy());
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["status"], "pass");
+        assert_eq!(json["checks"][0]["name"], "Startup");
+        assert!(json["checks"][0].get("reason").is_none());
+    }
+}
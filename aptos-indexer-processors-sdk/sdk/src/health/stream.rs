@@ -0,0 +1,226 @@
+//! Transaction stream health checking for processors.
+
+use super::core::HealthCheck;
+use crate::{
+    aptos_indexer_transaction_stream::StreamConnectionStats,
+    utils::pipeline_status::{pause_controller, PauseController},
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Configuration for transaction stream health checking.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StreamHealthConfig {
+    /// The number of seconds the stream is allowed to go without delivering a batch before
+    /// it's considered unhealthy.
+    #[serde(default = "default_max_receive_gap_secs")]
+    pub max_receive_gap_secs: u64,
+    /// The stream is considered unhealthy if it reconnected at least this many times within
+    /// `reconnect_storm_window_secs`. The data service closes connections every few minutes,
+    /// so occasional reconnects are normal.
+    #[serde(default = "default_reconnect_storm_threshold")]
+    pub reconnect_storm_threshold: usize,
+    #[serde(default = "default_reconnect_storm_window_secs")]
+    pub reconnect_storm_window_secs: u64,
+}
+
+pub const fn default_max_receive_gap_secs() -> u64 {
+    60
+}
+
+pub const fn default_reconnect_storm_threshold() -> usize {
+    5
+}
+
+pub const fn default_reconnect_storm_window_secs() -> u64 {
+    60
+}
+
+impl Default for StreamHealthConfig {
+    fn default() -> Self {
+        Self {
+            max_receive_gap_secs: default_max_receive_gap_secs(),
+            reconnect_storm_threshold: default_reconnect_storm_threshold(),
+            reconnect_storm_window_secs: default_reconnect_storm_window_secs(),
+        }
+    }
+}
+
+/// A health check that verifies the transaction stream is delivering transactions and isn't
+/// stuck reconnecting.
+///
+/// Pass the same `StreamConnectionStats` to `TransactionStreamStep::with_connection_stats`.
+/// Until the stream has connected, the check passes. It also passes while the pipeline is
+/// paused and for `max_receive_gap_secs` after it resumes.
+pub struct StreamHealthChecker {
+    connection_stats: StreamConnectionStats,
+    config: StreamHealthConfig,
+    /// New configs to apply, see `with_config_updates`.
+    config_updates: Option<watch::Receiver<StreamHealthConfig>>,
+    pause_controller: &'static PauseController,
+}
+
+impl StreamHealthChecker {
+    pub fn new(connection_stats: StreamConnectionStats, config: StreamHealthConfig) -> Self {
+        Self {
+            connection_stats,
+            config,
+            config_updates: None,
+            pause_controller: pause_controller(),
+        }
+    }
+
+    /// Uses the latest config sent to `config_updates`, e.g. from
+    /// `config_reload::ConfigReloader::subscribe`, instead of the initial one.
+    pub fn with_config_updates(
+        mut self,
+        config_updates: watch::Receiver<StreamHealthConfig>,
+    ) -> Self {
+        self.config_updates = Some(config_updates);
+        self
+    }
+
+    fn config(&self) -> StreamHealthConfig {
+        match &self.config_updates {
+            Some(config_updates) => config_updates.borrow().clone(),
+            None => self.config.clone(),
+        }
+    }
+
+    /// Checks the number of reconnects within `reconnect_storm_window_secs` and the time since
+    /// the stream last delivered a batch, or connected if it hasn't delivered one yet.
+    fn check(
+        &self,
+        reconnects: usize,
+        since_last_received: Option<Duration>,
+    ) -> Result<(), String> {
+        let config = self.config();
+        if self
+            .pause_controller
+            .paused_within(Duration::from_secs(config.max_receive_gap_secs))
+        {
+            return Ok(());
+        }
+        if reconnects >= config.reconnect_storm_threshold {
+            warn!(
+                stream_address = ?self.connection_stats.stream_address(),
+                reconnects,
+                window_secs = config.reconnect_storm_window_secs,
+                "Transaction stream is reconnecting repeatedly"
+            );
+            return Err(format!(
+                "Reconnected {} times in the last {} seconds (threshold: {})",
+                reconnects, config.reconnect_storm_window_secs, config.reconnect_storm_threshold
+            ));
+        }
+
+        let Some(since_last_received) = since_last_received else {
+            return Ok(());
+        };
+        let seconds_since_receive = since_last_received.as_secs();
+        if seconds_since_receive > config.max_receive_gap_secs {
+            warn!(
+                stream_address = ?self.connection_stats.stream_address(),
+                seconds_since_receive,
+                "Transaction stream has not delivered transactions within timeout"
+            );
+            return Err(format!(
+                "Last received transactions {} seconds ago (threshold: {} seconds)",
+                seconds_since_receive, config.max_receive_gap_secs
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HealthCheck for StreamHealthChecker {
+    fn name(&self) -> &str {
+        "TransactionStream"
+    }
+
+    async fn is_healthy(&self) -> Result<(), String> {
+        let reconnects = self.connection_stats.reconnects_within(Duration::from_secs(
+            self.config().reconnect_storm_window_secs,
+        ));
+        // Before the first batch, measure from when the stream connected.
+        let since_last_received = self
+            .connection_stats
+            .last_received_at()
+            .or(self.connection_stats.connected_at())
+            .map(|last_received_at| last_received_at.elapsed());
+        self.check(reconnects, since_last_received)
+    }
+
+    async fn status(&self) -> Option<String> {
+        if self.pause_controller.is_paused() {
+            return Some("Paused".to_string());
+        }
+        let stream_address = self.connection_stats.stream_address()?;
+        Some(format!(
+            "Connected to {} (connection id: {})",
+            stream_address,
+            self.connection_stats.connection_id().unwrap_or_default()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> StreamHealthChecker {
+        let mut checker = StreamHealthChecker::new(StreamConnectionStats::new(), StreamHealthConfig {
+            max_receive_gap_secs: 60,
+            reconnect_storm_threshold: 5,
+            reconnect_storm_window_secs: 60,
+        });
+        // A controller of its own, so that other tests don't see the pause.
+        checker.pause_controller = Box::leak(Box::new(PauseController::new()));
+        checker
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_stream_receive_gap() {
+        let check = checker();
+        // Not connected yet.
+        assert!(check.is_healthy().await.is_ok());
+        assert_eq!(check.status().await, None);
+
+        assert!(check.check(0, Some(Duration::from_secs(30))).is_ok());
+        let error = check.check(0, Some(Duration::from_secs(90))).unwrap_err();
+        assert!(error.contains("Last received transactions 90 seconds ago"));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_stream_reconnect_storm() {
+        let check = checker();
+        assert!(check.check(4, Some(Duration::from_secs(1))).is_ok());
+        let error = check.check(5, Some(Duration::from_secs(1))).unwrap_err();
+        assert!(error.contains("Reconnected 5 times in the last 60 seconds"));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_stream_passes_while_paused() {
+        let check = checker();
+        check.pause_controller.pause();
+        assert!(check.check(5, Some(Duration::from_secs(90))).is_ok());
+        assert_eq!(check.status().await.as_deref(), Some("Paused"));
+
+        // The stream gets `max_receive_gap_secs` to deliver again after a resume.
+        check.pause_controller.resume();
+        assert!(check.check(5, Some(Duration::from_secs(90))).is_ok());
+
+        let mut check = checker();
+        check.config.max_receive_gap_secs = 0;
+        check.pause_controller.pause();
+        check.pause_controller.resume();
+        assert!(check.check(0, Some(Duration::from_secs(90))).is_err());
+    }
+}
@@ -1,11 +1,20 @@
+#[cfg(feature = "server_framework")]
+pub mod admin;
 pub mod builder;
 pub mod common_steps; // TODO: Feature gate this?
 #[cfg(feature = "server_framework")]
+pub mod config_reload;
+#[cfg(feature = "server_framework")]
 pub mod health;
+pub mod logging;
+#[cfg(feature = "server_framework")]
+pub mod otel;
 #[cfg(feature = "postgres_partial")]
 pub mod postgres;
 #[cfg(feature = "server_framework")]
 pub mod server_framework;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 pub mod test;
 #[cfg(feature = "testing_framework")]
 pub mod testing_framework;
@@ -19,6 +28,8 @@ pub use aptos_protos;
 pub use aptos_transaction_filter;
 pub use bcs;
 pub use instrumented_channel;
+pub use sample;
+pub use tracing;
 
 #[cfg(test)]
 mod tests {
@@ -127,6 +138,7 @@ mod tests {
                 start_transaction_timestamp: None,
                 end_transaction_timestamp: None,
                 total_size_in_bytes: 0,
+                span: Default::default(),
             },
         };
         input_sender.send(left_input.clone()).await.unwrap();
@@ -209,6 +221,7 @@ mod tests {
                 start_transaction_timestamp: None,
                 end_transaction_timestamp: None,
                 total_size_in_bytes: 0,
+                span: Default::default(),
             },
         };
         input_sender.send(left_input.clone()).await.unwrap();
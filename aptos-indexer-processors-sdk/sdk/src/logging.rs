@@ -0,0 +1,159 @@
+//! Logging settings and sampled logging.
+//!
+//! The `logging_config` section of `GenericConfig` picks the log format, the log level per
+//! target and the sampling policy of hot logs. Hot logs are logged through `sampled_info!` and
+//! friends, or `sample::sample_with_policy!`, under a key, e.g.
+//! `transaction_stream.received_transactions`. Each key is logged at the rate set for it in
+//! `sampling`, or at the rate given at the call site if there is none.
+//!
+//! ```yaml
+//! logging_config:
+//!   format: compact
+//!   levels:
+//!     aptos_indexer_processor_sdk::common_steps: debug
+//!   sampling:
+//!     transaction_stream.received_transactions:
+//!       every_secs: 10
+//!     my_processor.parsed_events:
+//!       one_in: 1000
+//! ```
+//!
+//! Everything but the format can be reloaded, see `config_reload`.
+
+use sample::SampleRate;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// `EnvFilter` directives, e.g. `info,aptos_indexer_processor_sdk=debug`. Overrides
+    /// `RUST_LOG`.
+    #[serde(default)]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub format: LogFormat,
+    /// Log level per target, e.g. `aptos_indexer_transaction_stream: warn`. Applied on top of
+    /// `filter`.
+    #[serde(default)]
+    pub levels: BTreeMap<String, String>,
+    /// Rate per sampling key, overriding the rate given at the call site.
+    #[serde(default)]
+    pub sampling: BTreeMap<String, LogSampleRate>,
+}
+
+impl LoggingConfig {
+    /// The `sampling` policy, for `sample::set_sample_rates`.
+    pub fn sample_rates(&self) -> impl Iterator<Item = (String, SampleRate)> + '_ {
+        self.sampling
+            .iter()
+            .map(|(key, rate)| (key.clone(), rate.into()))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log aggregation.
+    #[default]
+    Json,
+    /// Multi-line, human-readable output, for local development.
+    Pretty,
+    /// Single-line, human-readable output.
+    Compact,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogSampleRate {
+    /// At most once every this many seconds.
+    EverySecs(u64),
+    /// Once every this many times.
+    OneIn(u64),
+    /// Every time, i.e. not sampled.
+    Always,
+}
+
+impl From<&LogSampleRate> for SampleRate {
+    fn from(rate: &LogSampleRate) -> Self {
+        match rate {
+            LogSampleRate::EverySecs(secs) => SampleRate::Duration(Duration::from_secs(*secs)),
+            LogSampleRate::OneIn(n) => SampleRate::Frequency(*n),
+            LogSampleRate::Always => SampleRate::Always,
+        }
+    }
+}
+
+/// Logs at info level, sampled under `key`: at the rate set for `key` in
+/// `logging_config.sampling`, or at `default_rate` if there is none.
+///
+/// ```
+/// use aptos_indexer_processor_sdk::{sample::SampleRate, sampled_info};
+/// use std::time::Duration;
+///
+/// let num_events = 10;
+/// sampled_info!(
+///     "my_processor.parsed_events",
+///     SampleRate::Duration(Duration::from_secs(5)),
+///     num_events,
+///     "Parsed events"
+/// );
+/// ```
+#[macro_export]
+macro_rules! sampled_info {
+    ($key:expr, $default_rate:expr, $($arg:tt)+) => {
+        $crate::sample::sample_with_policy!(
+            $key,
+            $default_rate,
+            $crate::tracing::info!($($arg)+)
+        )
+    };
+}
+
+/// Logs at warn level, sampled under `key`, see `sampled_info!`.
+#[macro_export]
+macro_rules! sampled_warn {
+    ($key:expr, $default_rate:expr, $($arg:tt)+) => {
+        $crate::sample::sample_with_policy!(
+            $key,
+            $default_rate,
+            $crate::tracing::warn!($($arg)+)
+        )
+    };
+}
+
+/// Logs at debug level, sampled under `key`, see `sampled_info!`.
+#[macro_export]
+macro_rules! sampled_debug {
+    ($key:expr, $default_rate:expr, $($arg:tt)+) => {
+        $crate::sample::sample_with_policy!(
+            $key,
+            $default_rate,
+            $crate::tracing::debug!($($arg)+)
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logging_config() {
+        let logging_config: LoggingConfig = serde_yaml::from_str(
+            "filter: warn\nformat: compact\nlevels:\n  aptos_indexer_transaction_stream: debug\nsampling:\n  a:\n    every_secs: 10\n  b:\n    one_in: 100\n  c: always\n",
+        )
+        .unwrap();
+        assert_eq!(logging_config.format, LogFormat::Compact);
+        assert_eq!(logging_config.filter.as_deref(), Some("warn"));
+        assert_eq!(logging_config.sample_rates().collect::<Vec<_>>(), vec![
+            (
+                "a".to_string(),
+                SampleRate::Duration(Duration::from_secs(10))
+            ),
+            ("b".to_string(), SampleRate::Frequency(100)),
+            ("c".to_string(), SampleRate::Always),
+        ]);
+        assert_eq!(LoggingConfig::default().format, LogFormat::Json);
+    }
+}
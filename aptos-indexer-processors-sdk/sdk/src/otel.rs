@@ -0,0 +1,231 @@
+//! Export of tracing spans to an OpenTelemetry collector over OTLP/gRPC.
+//!
+//! Every batch of transactions gets a span when it enters the pipeline, see
+//! `TransactionMetadata::span`, and every step's `process` call on the batch is traced as a
+//! child of it. With `otlp_config` set, these spans are exported along with any spans created
+//! while processing, e.g. around DB writes, so one version range can be followed across steps
+//! and the gaps between them show the time spent waiting in channels.
+//!
+//! Exporting requires the `otel` feature.
+
+#[cfg(not(feature = "otel"))]
+use anyhow::bail;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, Layer};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpConfig {
+    /// gRPC endpoint of the collector.
+    #[serde(default = "OtlpConfig::default_endpoint")]
+    pub endpoint: String,
+    /// `service.name` of the exported spans.
+    #[serde(default = "OtlpConfig::default_service_name")]
+    pub service_name: String,
+    /// Fraction of batches to trace, between 0 and 1.
+    #[serde(default = "OtlpConfig::default_sample_ratio")]
+    pub sample_ratio: f64,
+    #[serde(default = "OtlpConfig::default_export_timeout_secs")]
+    pub export_timeout_secs: u64,
+}
+
+impl OtlpConfig {
+    pub fn default_endpoint() -> String {
+        "http://localhost:4317".to_string()
+    }
+
+    pub fn default_service_name() -> String {
+        "aptos-indexer-processor".to_string()
+    }
+
+    pub const fn default_sample_ratio() -> f64 {
+        1.0
+    }
+
+    pub const fn default_export_timeout_secs() -> u64 {
+        10
+    }
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: Self::default_endpoint(),
+            service_name: Self::default_service_name(),
+            sample_ratio: Self::default_sample_ratio(),
+            export_timeout_secs: Self::default_export_timeout_secs(),
+        }
+    }
+}
+
+/// Creates a tracer provider that exports spans to `otlp_config.endpoint` in batches. Must be
+/// called from within the tokio runtime, which runs the exports.
+#[cfg(feature = "otel")]
+pub fn otlp_tracer_provider(
+    otlp_config: &OtlpConfig,
+) -> Result<opentelemetry_sdk::trace::TracerProvider> {
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{runtime, trace::Sampler, Resource};
+
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&otlp_config.endpoint)
+        .with_timeout(std::time::Duration::from_secs(
+            otlp_config.export_timeout_secs,
+        ))
+        .build()?;
+    Ok(opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        // Child spans follow the sampling decision of their batch.
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            otlp_config.sample_ratio,
+        ))))
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            otlp_config.service_name.clone(),
+        )]))
+        .build())
+}
+
+/// Builds the layer that exports spans as configured by `otlp_config`, if set, and installs
+/// its tracer provider globally so that `shutdown` can flush it.
+#[cfg(feature = "otel")]
+pub fn otlp_layer<S>(otlp_config: Option<&OtlpConfig>) -> Result<Option<impl Layer<S>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    use opentelemetry::trace::TracerProvider;
+
+    let Some(otlp_config) = otlp_config else {
+        return Ok(None);
+    };
+    let tracer_provider = otlp_tracer_provider(otlp_config)?;
+    let tracer = tracer_provider.tracer("aptos-indexer-processor-sdk");
+    opentelemetry::global::set_tracer_provider(tracer_provider);
+    Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+}
+
+#[cfg(not(feature = "otel"))]
+pub fn otlp_layer<S>(otlp_config: Option<&OtlpConfig>) -> Result<Option<impl Layer<S>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    if otlp_config.is_some() {
+        bail!("`otlp_config` requires the `otel` feature of aptos-indexer-processor-sdk");
+    }
+    Ok(None::<tracing_subscriber::layer::Identity>)
+}
+
+/// Exports the spans that haven't been exported yet. Call before the process exits.
+pub async fn shutdown() {
+    #[cfg(feature = "otel")]
+    {
+        // Blocks until the background exporter is done.
+        let _ = tokio::task::spawn_blocking(opentelemetry::global::shutdown_tracer_provider).await;
+    }
+}
+
+#[cfg(all(test, feature = "otel"))]
+mod tests {
+    use super::*;
+    use crate::{
+        test::{steps::pass_through_step::PassThroughStep, utils::receive_with_timeout},
+        traits::{RunnableAsyncStep, RunnableStep},
+        types::transaction_context::{TransactionContext, TransactionMetadata},
+    };
+    use instrumented_channel::instrumented_bounded_channel;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_proto::tonic::collector::trace::v1::{
+        trace_service_server::{TraceService, TraceServiceServer},
+        ExportTraceServiceRequest, ExportTraceServiceResponse,
+    };
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // Stands in for the collector, keeping the name, span id and parent span id of every span.
+    #[derive(Clone, Default)]
+    struct TestCollector {
+        spans: Arc<Mutex<Vec<(String, Vec<u8>, Vec<u8>)>>>,
+    }
+
+    #[tonic::async_trait]
+    impl TraceService for TestCollector {
+        async fn export(
+            &self,
+            request: tonic::Request<ExportTraceServiceRequest>,
+        ) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+            let mut spans = self.spans.lock().unwrap();
+            for resource_spans in request.into_inner().resource_spans {
+                for scope_spans in resource_spans.scope_spans {
+                    for span in scope_spans.spans {
+                        spans.push((span.name, span.span_id, span.parent_span_id));
+                    }
+                }
+            }
+            Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_export_batch_spans() {
+        let collector = TestCollector::default();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(TraceServiceServer::new(collector.clone()))
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+        );
+
+        let tracer_provider = otlp_tracer_provider(&OtlpConfig {
+            endpoint,
+            ..OtlpConfig::default()
+        })
+        .unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let (input_sender, input_receiver) = instrumented_bounded_channel("input", 1);
+        let (mut output_receiver, _handle) = RunnableAsyncStep::new(PassThroughStep::new_named(
+            "TracedStep".to_string(),
+        ))
+        .spawn(Some(input_receiver), 1, None);
+        input_sender
+            .send(TransactionContext {
+                data: vec![1, 2, 3],
+                metadata: TransactionMetadata {
+                    start_version: 100,
+                    end_version: 102,
+                    span: TransactionMetadata::batch_span(100, 102),
+                    ..TransactionMetadata::default()
+                },
+            })
+            .await
+            .unwrap();
+        // The batch span ends once the last step is done with the batch.
+        drop(
+            receive_with_timeout(&mut output_receiver, 1000)
+                .await
+                .unwrap(),
+        );
+
+        tokio::task::spawn_blocking(move || tracer_provider.force_flush())
+            .await
+            .unwrap();
+        let spans = collector.spans.lock().unwrap().clone();
+        let (_, batch_span_id, _) = spans
+            .iter()
+            .find(|(name, ..)| name == "batch")
+            .expect("Batch span was not exported");
+        let (_, _, step_parent_span_id) = spans
+            .iter()
+            .find(|(name, ..)| name == "TracedStep")
+            .expect("Step span was not exported");
+        assert_eq!(step_parent_span_id, batch_span_id);
+    }
+}
@@ -1,24 +1,33 @@
 use super::basic_processor_step::BasicProcessorStep;
 use crate::{
-    aptos_indexer_transaction_stream::TransactionStreamConfig,
+    admin::admin_router,
+    aptos_indexer_transaction_stream::{StreamConnectionStats, TransactionStreamConfig},
     builder::ProcessorBuilder,
     common_steps::{
-        TransactionStreamStep, VersionTrackerStep, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS,
+        ShardFilterStep, ShardingConfig, TransactionStreamStep, VersionTrackerStep,
+        DEFAULT_UPDATE_PROCESSOR_STATUS_SECS,
     },
+    config_reload::config_reloader,
+    otel,
     postgres::{
-        progress::PostgresProgressStatusProvider,
-        subconfigs::postgres_config::PostgresConfig,
+        leader_election::{LeaderElectionConfig, PostgresLeaderElection},
+        pool_health::{DbPoolHealthChecker, DbPoolHealthConfig},
+        progress::{PostgresBackfillProgressStatusProvider, PostgresProgressStatusProvider},
+        subconfigs::{backfill_config::BackfillConfig, postgres_config::PostgresConfig},
         utils::{
             checkpoint::{
-                get_starting_version, PostgresChainIdChecker, PostgresProcessorStatusSaver,
+                get_backfill_starting_version, get_starting_version, PostgresBackfillStatusSaver,
+                PostgresChainIdChecker, PostgresProcessorStatusSaver, PostgresStatusSaver,
             },
-            database::{new_db_pool, run_migrations, ArcDbPool},
+            database::{new_db_pool, run_user_and_sdk_migrations, ArcDbPool},
+            rewind::{rewind_processor, RewindHook},
         },
-        SDK_MIGRATIONS,
     },
     server_framework::{
-        load, register_probes_and_metrics_handler, setup_logging, setup_panic_handler,
-        GenericConfig, HealthCheck, ProgressHealthChecker, ProgressHealthConfig, ServerArgs,
+        register_probes_metrics_and_admin_handler, setup_logging_with_config, setup_panic_handler,
+        ChainLagHealthChecker, ChainLagHealthConfig, GenericConfig, HealthCheck, HealthChecks,
+        ProgressHealthChecker, ProgressHealthConfig, ProgressStatusProvider, ReadinessGate,
+        ServerArgs, ServerCommand, StreamHealthChecker, StreamHealthConfig,
     },
     traits::IntoRunnableStep,
     utils::{chain_id_check::check_or_update_chain_id, errors::ProcessorError},
@@ -37,9 +46,44 @@ pub struct ProcessConfig {
     pub transaction_stream_config: TransactionStreamConfig,
     pub postgres_config: PostgresConfig,
     /// Optional configuration for progress health checking.
-    /// If provided, the `/healthz` endpoint will check if the processor is making progress.
+    /// If provided, the `/livez` and `/healthz` endpoints will check if the processor is making
+    /// progress. A processor that stopped making progress is wedged, so this is a liveness
+    /// check and failing it restarts the pod.
     #[serde(default)]
     pub progress_health_config: Option<ProgressHealthConfig>,
+    /// Optional configuration for chain lag health checking.
+    /// If provided, the `/readyz` endpoint will check that the last processed transaction
+    /// isn't too far behind the current time. Skipped in backfill mode, as a backfill replays
+    /// old transactions.
+    #[serde(default)]
+    pub chain_lag_health_config: Option<ChainLagHealthConfig>,
+    /// Optional configuration for transaction stream health checking.
+    /// If provided, the `/readyz` endpoint will check that the stream keeps delivering
+    /// transactions and isn't reconnecting repeatedly.
+    #[serde(default)]
+    pub stream_health_config: Option<StreamHealthConfig>,
+    /// Optional configuration for connection pool health checking.
+    /// If provided, the `/readyz` endpoint will check that the database is reachable through
+    /// the pool.
+    #[serde(default)]
+    pub db_pool_health_config: Option<DbPoolHealthConfig>,
+    /// Optional leader election for running several replicas of the same processor.
+    /// If provided, only the replica holding the processor's advisory lock runs the
+    /// pipeline, while the others wait on standby. In backfill mode, the lock is keyed on the
+    /// backfill id instead, so that a backfill doesn't compete with the live processor.
+    #[serde(default)]
+    pub leader_election_config: Option<LeaderElectionConfig>,
+    /// Optional sharding for splitting one processor across several instances. If provided,
+    /// this instance only processes its own shard and tracks its progress as
+    /// `processor_name#shard-i`.
+    #[serde(default)]
+    pub sharding_config: Option<ShardingConfig>,
+    /// Optional bounded backfill. If provided, the processor only processes
+    /// `[backfill_start_version, backfill_end_version]`, tracks its progress in
+    /// `backfill_processor_status` and exits once the backfill is complete. The progress health
+    /// check then reads the backfill's row.
+    #[serde(default)]
+    pub backfill_config: Option<BackfillConfig>,
 }
 
 /// Processes transactions with a custom handler function.
@@ -48,19 +92,58 @@ pub async fn process<F, Fut>(
     embedded_migrations: EmbeddedMigrations,
     process_function: F,
 ) -> Result<()>
+where
+    F: FnMut(Vec<Transaction>, ArcDbPool) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
+{
+    process_with_rewind_hooks(
+        processor_name,
+        embedded_migrations,
+        vec![],
+        process_function,
+    )
+    .await
+}
+
+/// Same as `process`, but with the hooks that clean up the processor's tables when it is
+/// started with the `rewind` subcommand.
+pub async fn process_with_rewind_hooks<F, Fut>(
+    processor_name: String,
+    embedded_migrations: EmbeddedMigrations,
+    rewind_hooks: Vec<Box<dyn RewindHook>>,
+    process_function: F,
+) -> Result<()>
 where
     F: FnMut(Vec<Transaction>, ArcDbPool) -> Fut + Send + Sync + 'static,
     Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
 {
     let args = ServerArgs::parse();
-    setup_logging();
+    let config = args.load_config::<GenericConfig<ProcessConfig>>()?;
+    setup_logging_with_config(&config.logging_config, config.otlp_config.as_ref())?;
     setup_panic_handler();
-    let config = load::<GenericConfig<ProcessConfig>>(&args.config_path)?;
     let handle = tokio::runtime::Handle::current();
 
+    // Each shard tracks its own progress.
+    let processor_name = match &config.server_config.sharding_config {
+        Some(sharding_config) => sharding_config.shard_processor_name(&processor_name),
+        None => processor_name,
+    };
+    let backfill_id = config
+        .server_config
+        .backfill_config
+        .clone()
+        .map(|backfill_config| {
+            backfill_config
+                .for_shard(config.server_config.sharding_config.as_ref())
+                .backfill_id
+        });
+
     let health_port = config.health_check_port;
     let additional_labels = config.metrics_config.additional_labels.clone();
     let progress_health_config = config.server_config.progress_health_config.clone();
+    let chain_lag_health_config = config.server_config.chain_lag_health_config.clone();
+    let stream_health_config = config.server_config.stream_health_config.clone();
+    let db_pool_health_config = config.server_config.db_pool_health_config.clone();
 
     let db_pool = new_db_pool(
         &config.server_config.postgres_config.connection_string,
@@ -69,22 +152,117 @@ where
     .await
     .expect("Failed to create connection pool");
 
-    // Build health checks.
-    let mut health_checks: Vec<Arc<dyn HealthCheck>> = vec![];
+    if let Some(ServerCommand::Rewind(rewind_args)) = &args.command {
+        rewind_processor(&processor_name, rewind_args.into(), &rewind_hooks, db_pool).await?;
+        return Ok(());
+    }
+
+    args.start_config_reload(&config)?;
+
+    // Build health checks. Only a wedged processor fails liveness and gets restarted. Failing
+    // dependencies like the DB or the stream take the pod out of rotation until they recover.
+    let mut liveness_checks: Vec<Arc<dyn HealthCheck>> = vec![];
+    let mut readiness_checks: Vec<Arc<dyn HealthCheck>> = vec![];
     if let Some(progress_config) = progress_health_config {
-        let status_provider =
+        // A backfill checkpoints to its own row instead of the processor's.
+        let status_provider: Box<dyn ProgressStatusProvider> = match &backfill_id {
+            Some(backfill_id) => Box::new(PostgresBackfillProgressStatusProvider::new(
+                backfill_id.clone(),
+                db_pool.clone(),
+            )),
+            None => Box::new(PostgresProgressStatusProvider::new(
+                processor_name.clone(),
+                db_pool.clone(),
+            )),
+        };
+        let mut progress_checker =
+            ProgressHealthChecker::new(processor_name.clone(), status_provider, progress_config);
+        if let Some(config_reloader) = config_reloader() {
+            progress_checker = progress_checker.with_config_updates(
+                config_reloader.subscribe("server_config.progress_health_config")?,
+            );
+        }
+        liveness_checks.push(Arc::new(progress_checker));
+    }
+    if backfill_id.is_some() && chain_lag_health_config.is_some() {
+        // A backfill replays old transactions, so it is always far behind the chain.
+        info!("Skipping the chain lag health check while backfilling");
+    } else if let Some(chain_lag_config) = chain_lag_health_config {
+        let timestamp_provider =
             PostgresProgressStatusProvider::new(processor_name.clone(), db_pool.clone());
-        let progress_checker = ProgressHealthChecker::new(
+        let mut chain_lag_checker = ChainLagHealthChecker::new(
             processor_name.clone(),
-            Box::new(status_provider),
-            progress_config,
+            Box::new(timestamp_provider),
+            chain_lag_config,
         );
-        health_checks.push(Arc::new(progress_checker));
+        if let Some(config_reloader) = config_reloader() {
+            chain_lag_checker = chain_lag_checker.with_config_updates(
+                config_reloader.subscribe("server_config.chain_lag_health_config")?,
+            );
+        }
+        readiness_checks.push(Arc::new(chain_lag_checker));
     }
+    let stream_connection_stats = match stream_health_config {
+        Some(stream_health_config) => {
+            let stream_connection_stats = StreamConnectionStats::new();
+            let mut stream_checker =
+                StreamHealthChecker::new(stream_connection_stats.clone(), stream_health_config);
+            if let Some(config_reloader) = config_reloader() {
+                stream_checker = stream_checker.with_config_updates(
+                    config_reloader.subscribe("server_config.stream_health_config")?,
+                );
+            }
+            readiness_checks.push(Arc::new(stream_checker));
+            Some(stream_connection_stats)
+        },
+        None => None,
+    };
+    if let Some(db_pool_health_config) = db_pool_health_config {
+        readiness_checks.push(Arc::new(DbPoolHealthChecker::new(
+            db_pool.clone(),
+            db_pool_health_config,
+        )));
+    }
+    let leader_election =
+        config
+            .server_config
+            .leader_election_config
+            .clone()
+            .map(|leader_election_config| {
+                // Replicas of a backfill compete for the backfill, not the live processor.
+                PostgresLeaderElection::new(
+                    backfill_id.as_deref().unwrap_or(&processor_name),
+                    db_pool.clone(),
+                    leader_election_config,
+                )
+            });
+    if let Some(leader_election) = &leader_election {
+        readiness_checks.push(Arc::new(leader_election.health_check()));
+    }
+
+    // Not ready until migrations have run and the chain id is verified.
+    let readiness_gate = ReadinessGate::new("Startup");
+    readiness_checks.push(Arc::new(readiness_gate.clone()));
+    let health_checks = HealthChecks {
+        liveness: liveness_checks,
+        readiness: readiness_checks,
+        ..HealthChecks::default()
+    };
 
-    // Start health and metrics probes.
+    let admin_router = config
+        .admin_config
+        .as_ref()
+        .map(|admin_config| admin_router(admin_config, serde_json::to_value(&config).ok()));
+
+    // Start health, metrics and admin probes.
     let task_handler = handle.spawn(async move {
-        register_probes_and_metrics_handler(health_port, additional_labels, health_checks).await;
+        register_probes_metrics_and_admin_handler(
+            health_port,
+            additional_labels,
+            health_checks,
+            admin_router,
+        )
+        .await;
         anyhow::Ok(())
     });
     let main_task_handler = handle.spawn(async move {
@@ -95,19 +273,48 @@ where
             embedded_migrations,
             db_pool,
             process_function,
+            RunProcessorOptions {
+                sharding_config: config.server_config.sharding_config,
+                backfill_config: config.server_config.backfill_config,
+                readiness_gate: Some(readiness_gate),
+                stream_connection_stats,
+                leader_election: leader_election.as_ref(),
+            },
         )
         .await
     });
-    tokio::select! {
+    let res = tokio::select! {
         res = task_handler => {
             res.expect("Probes and metrics handler unexpectedly exited")
         },
         res = main_task_handler => {
             res.expect("Main task handler unexpectedly exited")
         },
-    }
+    };
+    otel::shutdown().await;
+    res
+}
+
+/// Optional parts of the pipeline run by `run_processor`. Everything is off by default.
+#[derive(Default)]
+pub struct RunProcessorOptions<'a> {
+    /// Only processes this instance's shard. `processor_name` is then expected to already be
+    /// the shard's name, see `ShardingConfig::shard_processor_name`.
+    pub sharding_config: Option<ShardingConfig>,
+    /// Stops the pipeline at the end of the backfill range. With sharding, each shard tracks
+    /// the backfill under its own id, e.g. `backfill_id#shard-2`.
+    pub backfill_config: Option<BackfillConfig>,
+    /// Marked ready once the migrations have run and the chain id is verified.
+    pub readiness_gate: Option<ReadinessGate>,
+    /// Connection stats of the transaction stream, for `StreamHealthChecker`.
+    pub stream_connection_stats: Option<StreamConnectionStats>,
+    /// Only runs the pipeline while this replica holds the leader lock. Standbys still run
+    /// the migrations and the chain id check and then mark `readiness_gate` ready, as they are
+    /// healthy and able to take over at any time.
+    pub leader_election: Option<&'a PostgresLeaderElection>,
 }
 
+/// Runs the processor pipeline, with the optional parts set in `options`.
 pub async fn run_processor<F, Fut>(
     processor_name: String,
     transaction_stream_config: TransactionStreamConfig,
@@ -115,24 +322,25 @@ pub async fn run_processor<F, Fut>(
     embedded_migrations: EmbeddedMigrations,
     db_pool: ArcDbPool,
     process_function: F,
+    options: RunProcessorOptions<'_>,
 ) -> Result<()>
 where
     F: FnMut(Vec<Transaction>, ArcDbPool) -> Fut + Send + Sync + 'static,
     Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
 {
-    // Run user migrations.
-    run_migrations(
-        postgres_config.connection_string.clone(),
-        db_pool.clone(),
-        embedded_migrations,
-    )
-    .await;
+    let RunProcessorOptions {
+        sharding_config,
+        backfill_config,
+        readiness_gate,
+        stream_connection_stats,
+        leader_election,
+    } = options;
 
-    // Run SDK migrations.
-    run_migrations(
+    // Run user migrations, then SDK migrations.
+    run_user_and_sdk_migrations(
         postgres_config.connection_string.clone(),
         db_pool.clone(),
-        SDK_MIGRATIONS,
+        embedded_migrations,
     )
     .await;
 
@@ -141,37 +349,122 @@ where
         &PostgresChainIdChecker::new(db_pool.clone()),
     )
     .await?;
+    if let Some(readiness_gate) = &readiness_gate {
+        readiness_gate.mark_ready();
+    }
+
+    let pipeline = run_pipeline(
+        processor_name,
+        transaction_stream_config,
+        sharding_config,
+        backfill_config,
+        db_pool,
+        process_function,
+        stream_connection_stats,
+    );
+    match leader_election {
+        Some(leader_election) => {
+            // The checkpoint is only read once the lock is held, so that a new leader resumes
+            // from where the previous one stopped.
+            let mut lease = leader_election.acquire().await?;
+            tokio::select! {
+                res = pipeline => res,
+                e = lease.wait_until_lost() => Err(e.into()),
+            }
+        },
+        None => pipeline.await,
+    }
+}
+
+async fn run_pipeline<F, Fut>(
+    processor_name: String,
+    transaction_stream_config: TransactionStreamConfig,
+    sharding_config: Option<ShardingConfig>,
+    backfill_config: Option<BackfillConfig>,
+    db_pool: ArcDbPool,
+    process_function: F,
+    stream_connection_stats: Option<StreamConnectionStats>,
+) -> Result<()>
+where
+    F: FnMut(Vec<Transaction>, ArcDbPool) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
+{
+    let backfill_config =
+        backfill_config.map(|backfill_config| backfill_config.for_shard(sharding_config.as_ref()));
 
     // Merge the starting version from config and the latest processed version from the DB.
-    let starting_version = get_starting_version(
-        processor_name.as_str(),
-        transaction_stream_config.clone(),
-        db_pool.clone(),
-    )
-    .await?;
+    let transaction_stream_config = match &backfill_config {
+        Some(backfill_config) => {
+            let Some(starting_version) =
+                get_backfill_starting_version(backfill_config, db_pool.clone()).await?
+            else {
+                info!(
+                    backfill_id = backfill_config.backfill_id,
+                    "Backfill is already complete, nothing to do"
+                );
+                return Ok(());
+            };
+            TransactionStreamConfig {
+                starting_version: Some(starting_version),
+                request_ending_version: Some(backfill_config.backfill_end_version),
+                ..transaction_stream_config
+            }
+        },
+        None => {
+            let starting_version = get_starting_version(
+                processor_name.as_str(),
+                transaction_stream_config.clone(),
+                db_pool.clone(),
+            )
+            .await?;
+            TransactionStreamConfig {
+                starting_version: Some(starting_version),
+                ..transaction_stream_config
+            }
+        },
+    };
 
     // Define processor steps.
-    let transaction_stream_config = transaction_stream_config.clone();
-    let transaction_stream = TransactionStreamStep::new(TransactionStreamConfig {
-        starting_version: Some(starting_version),
-        ..transaction_stream_config
-    })
-    .await?;
+    let mut transaction_stream = TransactionStreamStep::new(transaction_stream_config).await?;
+    if let Some(stream_connection_stats) = stream_connection_stats {
+        transaction_stream = transaction_stream.with_connection_stats(stream_connection_stats);
+    }
     let basic_processor_step = BasicProcessorStep {
         process_function,
         conn_pool: db_pool.clone(),
     };
-    let processor_status_saver =
-        PostgresProcessorStatusSaver::new(processor_name.as_str(), db_pool.clone());
+    let processor_status_saver = match backfill_config {
+        Some(backfill_config) => PostgresStatusSaver::Backfill(PostgresBackfillStatusSaver::new(
+            backfill_config,
+            db_pool.clone(),
+        )),
+        None => PostgresStatusSaver::Processor(PostgresProcessorStatusSaver::new(
+            processor_name.as_str(),
+            db_pool.clone(),
+        )),
+    };
     let version_tracker =
         VersionTrackerStep::new(processor_status_saver, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS);
 
     // Connect processor steps together.
-    let (_, buffer_receiver) =
-        ProcessorBuilder::new_with_inputless_first_step(transaction_stream.into_runnable_step())
-            .connect_to(basic_processor_step.into_runnable_step(), 10)
-            .connect_to(version_tracker.into_runnable_step(), 10)
-            .end_and_return_output_receiver(10);
+    let (_, buffer_receiver) = match sharding_config {
+        Some(sharding_config) => {
+            ProcessorBuilder::new_with_inputless_first_step(transaction_stream.into_runnable_step())
+                .connect_to(
+                    ShardFilterStep::new(sharding_config)?.into_runnable_step(),
+                    10,
+                )
+                .connect_to(basic_processor_step.into_runnable_step(), 10)
+                .connect_to(version_tracker.into_runnable_step(), 10)
+                .end_and_return_output_receiver(10)
+        },
+        None => {
+            ProcessorBuilder::new_with_inputless_first_step(transaction_stream.into_runnable_step())
+                .connect_to(basic_processor_step.into_runnable_step(), 10)
+                .connect_to(version_tracker.into_runnable_step(), 10)
+                .end_and_return_output_receiver(10)
+        },
+    };
 
     // (Optional) Parse the results.
     loop {
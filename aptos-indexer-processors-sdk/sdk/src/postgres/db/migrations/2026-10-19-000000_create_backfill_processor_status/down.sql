@@ -0,0 +1 @@
+DROP TABLE IF EXISTS processor_metadata.backfill_processor_status;
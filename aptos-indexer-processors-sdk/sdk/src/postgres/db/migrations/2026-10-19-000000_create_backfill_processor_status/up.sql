@@ -0,0 +1,10 @@
+-- Tracks progress of bounded backfills, separately from the live processor's status
+CREATE TABLE IF NOT EXISTS processor_metadata.backfill_processor_status (
+  backfill_id VARCHAR(100) UNIQUE PRIMARY KEY NOT NULL,
+  backfill_status VARCHAR(50) NOT NULL,
+  last_success_version BIGINT NOT NULL,
+  last_updated TIMESTAMP NOT NULL DEFAULT NOW(),
+  last_transaction_timestamp TIMESTAMP NULL,
+  backfill_start_version BIGINT NOT NULL,
+  backfill_end_version BIGINT NOT NULL
+);
@@ -1,6 +1,20 @@
 // @generated automatically by Diesel CLI.
 
 pub mod processor_metadata {
+    diesel::table! {
+        processor_metadata.backfill_processor_status (backfill_id) {
+            #[max_length = 100]
+            backfill_id -> Varchar,
+            #[max_length = 50]
+            backfill_status -> Varchar,
+            last_success_version -> Int8,
+            last_updated -> Timestamp,
+            last_transaction_timestamp -> Nullable<Timestamp>,
+            backfill_start_version -> Int8,
+            backfill_end_version -> Int8,
+        }
+    }
+
     diesel::table! {
         processor_metadata.ledger_infos (chain_id) {
             chain_id -> Int8,
@@ -17,5 +31,9 @@ pub mod processor_metadata {
         }
     }
 
-    diesel::allow_tables_to_appear_in_same_query!(ledger_infos, processor_status,);
+    diesel::allow_tables_to_appear_in_same_query!(
+        backfill_processor_status,
+        ledger_infos,
+        processor_status,
+    );
 }
@@ -0,0 +1,283 @@
+//! Leader election for processors running with multiple replicas.
+//!
+//! Replicas of the same processor compete for a session-level Postgres advisory lock keyed
+//! by the processor name. The replica holding the lock is the leader and runs the pipeline,
+//! the others stay on standby and keep retrying. Since the lock is tied to the leader's
+//! connection, it is released as soon as the leader exits or loses its connection, at
+//! which point a standby takes over. Standbys report ready, as they are healthy and able to
+//! take over at any time.
+
+use crate::{
+    health::HealthCheck,
+    postgres::utils::database::{ArcDbPool, MyDbConnection},
+    utils::errors::ProcessorError,
+};
+use async_trait::async_trait;
+use diesel::{
+    sql_types::{BigInt, Bool},
+    QueryableByName,
+};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Configuration for leader election.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LeaderElectionConfig {
+    /// How often a standby tries to take the lock.
+    #[serde(default = "LeaderElectionConfig::default_retry_interval_secs")]
+    pub retry_interval_secs: u64,
+    /// How often the leader checks that the connection holding the lock is still alive.
+    #[serde(default = "LeaderElectionConfig::default_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+impl LeaderElectionConfig {
+    pub const fn default_retry_interval_secs() -> u64 {
+        5
+    }
+
+    pub const fn default_check_interval_secs() -> u64 {
+        5
+    }
+}
+
+impl Default for LeaderElectionConfig {
+    fn default() -> Self {
+        Self {
+            retry_interval_secs: Self::default_retry_interval_secs(),
+            check_interval_secs: Self::default_check_interval_secs(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeaderRole {
+    Standby,
+    Leader,
+}
+
+#[derive(QueryableByName)]
+struct TryLockResult {
+    #[diesel(sql_type = Bool)]
+    locked: bool,
+}
+
+/// Competes for the processor's advisory lock. See the module documentation for details.
+pub struct PostgresLeaderElection {
+    processor_name: String,
+    lock_id: i64,
+    db_pool: ArcDbPool,
+    config: LeaderElectionConfig,
+    role: watch::Sender<LeaderRole>,
+}
+
+impl PostgresLeaderElection {
+    pub fn new(processor_name: &str, db_pool: ArcDbPool, config: LeaderElectionConfig) -> Self {
+        let (role, _) = watch::channel(LeaderRole::Standby);
+        Self {
+            processor_name: processor_name.to_string(),
+            lock_id: leader_lock_id(processor_name),
+            db_pool,
+            config,
+            role,
+        }
+    }
+
+    pub fn role(&self) -> LeaderRole {
+        *self.role.borrow()
+    }
+
+    /// Returns a `HealthCheck` that reports whether this replica is the leader or on standby.
+    pub fn health_check(&self) -> LeaderElectionHealthCheck {
+        LeaderElectionHealthCheck {
+            role: self.role.subscribe(),
+        }
+    }
+
+    /// Waits until this replica holds the lock. The lock is held for as long as the
+    /// returned `LeaderLease` is alive.
+    pub async fn acquire(&self) -> Result<LeaderLease<'_>, ProcessorError> {
+        let mut conn = self
+            .db_pool
+            // The lock belongs to the session, so it needs a connection that is never
+            // handed back to the pool.
+            .dedicated_connection()
+            .await
+            .map_err(|e| ProcessorError::StepInitError {
+                message: format!("Error getting connection for leader election: {e:?}"),
+            })?;
+        loop {
+            let result = diesel::sql_query("SELECT pg_try_advisory_lock($1) AS locked")
+                .bind::<BigInt, _>(self.lock_id)
+                .get_result::<TryLockResult>(&mut conn)
+                .await
+                .map_err(|e| ProcessorError::StepInitError {
+                    message: format!("Error trying to acquire leader lock: {e:?}"),
+                })?;
+            if result.locked {
+                info!(
+                    processor = self.processor_name,
+                    lock_id = self.lock_id,
+                    "Acquired leader lock, this replica is now the leader"
+                );
+                self.role.send_replace(LeaderRole::Leader);
+                return Ok(LeaderLease {
+                    election: self,
+                    conn,
+                });
+            }
+            info!(
+                processor = self.processor_name,
+                lock_id = self.lock_id,
+                "Leader lock is held by another replica, staying on standby"
+            );
+            tokio::time::sleep(Duration::from_secs(self.config.retry_interval_secs)).await;
+        }
+    }
+}
+
+/// Proof of leadership. Dropping it closes the connection, which releases the lock.
+pub struct LeaderLease<'a> {
+    election: &'a PostgresLeaderElection,
+    conn: MyDbConnection,
+}
+
+impl LeaderLease<'_> {
+    /// Resolves once leadership is lost, i.e. once the connection holding the lock fails.
+    /// The pipeline must stop when this happens, since a standby may already have taken over.
+    pub async fn wait_until_lost(&mut self) -> ProcessorError {
+        let check_interval = Duration::from_secs(self.election.config.check_interval_secs);
+        loop {
+            tokio::time::sleep(check_interval).await;
+            if let Err(e) = diesel::sql_query("SELECT 1").execute(&mut self.conn).await {
+                warn!(
+                    processor = self.election.processor_name,
+                    error = ?e,
+                    "Lost the connection holding the leader lock"
+                );
+                self.election.role.send_replace(LeaderRole::Standby);
+                return ProcessorError::ProcessError {
+                    message: format!("Lost leadership: {e:?}"),
+                };
+            }
+        }
+    }
+
+    /// Explicitly releases the lock so that a standby can take over right away.
+    pub async fn release(mut self) -> Result<(), ProcessorError> {
+        diesel::sql_query("SELECT pg_advisory_unlock($1)")
+            .bind::<BigInt, _>(self.election.lock_id)
+            .execute(&mut self.conn)
+            .await
+            .map_err(|e| ProcessorError::ProcessError {
+                message: format!("Error releasing leader lock: {e:?}"),
+            })?;
+        self.election.role.send_replace(LeaderRole::Standby);
+        Ok(())
+    }
+}
+
+impl Drop for LeaderLease<'_> {
+    fn drop(&mut self) {
+        self.election.role.send_replace(LeaderRole::Standby);
+    }
+}
+
+/// Reports whether this replica is the leader or on standby. Both are healthy states, a
+/// standby is simply waiting for its turn.
+pub struct LeaderElectionHealthCheck {
+    role: watch::Receiver<LeaderRole>,
+}
+
+#[async_trait]
+impl HealthCheck for LeaderElectionHealthCheck {
+    fn name(&self) -> &str {
+        "LeaderElection"
+    }
+
+    async fn is_healthy(&self) -> Result<(), String> {
+        Ok(())
+    }
+
+    async fn status(&self) -> Option<String> {
+        match *self.role.borrow() {
+            LeaderRole::Leader => Some("leader".to_string()),
+            LeaderRole::Standby => Some("standby".to_string()),
+        }
+    }
+}
+
+/// Derives a stable advisory lock key from the processor name.
+pub fn leader_lock_id(processor_name: &str) -> i64 {
+    let hash = Sha256::digest(format!("leader_election:{processor_name}").as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash[..8]);
+    i64::from_be_bytes(bytes)
+}
+
+#[cfg(all(test, feature = "testing_framework"))]
+mod tests {
+    use super::*;
+    use crate::postgres::test_utils::setup_test_db;
+    use tokio::time::timeout;
+
+    fn config() -> LeaderElectionConfig {
+        LeaderElectionConfig {
+            retry_interval_secs: 1,
+            check_interval_secs: 1,
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_leader_election() {
+        let (_db, db_pool) = setup_test_db().await;
+        let first = PostgresLeaderElection::new("test", db_pool.clone(), config());
+        let second = PostgresLeaderElection::new("test", db_pool.clone(), config());
+
+        let first_lease = first.acquire().await.unwrap();
+        assert_eq!(first.role(), LeaderRole::Leader);
+
+        // The lock is taken, so the second replica stays on standby.
+        assert!(timeout(Duration::from_secs(3), second.acquire())
+            .await
+            .is_err());
+        assert_eq!(second.role(), LeaderRole::Standby);
+
+        // Once the leader exits, the standby takes over.
+        drop(first_lease);
+        assert_eq!(first.role(), LeaderRole::Standby);
+        let mut second_lease = timeout(Duration::from_secs(10), second.acquire())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.role(), LeaderRole::Leader);
+
+        // Killing the connection holding the lock loses leadership.
+        let mut conn = db_pool.get().await.unwrap();
+        diesel::sql_query(
+            "SELECT pg_terminate_backend(pid) FROM pg_locks \
+             WHERE locktype = 'advisory' AND pid <> pg_backend_pid()",
+        )
+        .execute(&mut conn)
+        .await
+        .unwrap();
+        timeout(Duration::from_secs(10), second_lease.wait_until_lost())
+            .await
+            .unwrap();
+        assert_eq!(second.role(), LeaderRole::Standby);
+
+        // The lock was released with the connection, so the first replica can take it again.
+        let first_lease = timeout(Duration::from_secs(10), first.acquire())
+            .await
+            .unwrap()
+            .unwrap();
+        first_lease.release().await.unwrap();
+        assert_eq!(first.role(), LeaderRole::Standby);
+    }
+}
@@ -1,9 +1,13 @@
 use diesel_migrations::{embed_migrations, EmbeddedMigrations};
 
 pub mod basic_processor;
+pub mod leader_election;
 pub mod models;
+pub mod pool_health;
 pub mod progress;
 pub mod subconfigs;
+#[cfg(all(test, feature = "testing_framework"))]
+pub(crate) mod test_utils;
 pub mod utils;
 
 #[path = "db/processor_metadata_schema.rs"]
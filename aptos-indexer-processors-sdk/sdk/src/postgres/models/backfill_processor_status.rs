@@ -0,0 +1,90 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::postgres::{
+    processor_metadata_schema::processor_metadata::backfill_processor_status,
+    utils::database::DbPoolConnection,
+};
+use diesel::{
+    deserialize::{self, FromSql, FromSqlRow},
+    expression::AsExpression,
+    pg::{Pg, PgValue},
+    serialize::{self, IsNull, Output, ToSql},
+    sql_types::Text,
+    AsChangeset, ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable,
+};
+use diesel_async::RunQueryDsl;
+use std::io::Write;
+
+#[derive(AsExpression, Clone, Copy, Debug, Eq, FromSqlRow, PartialEq)]
+#[diesel(sql_type = Text)]
+pub enum BackfillStatus {
+    InProgress,
+    Complete,
+}
+
+impl BackfillStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackfillStatus::InProgress => "in_progress",
+            BackfillStatus::Complete => "complete",
+        }
+    }
+}
+
+impl ToSql<Text, Pg> for BackfillStatus {
+    fn to_sql<'b>(&'b self, out: &mut Output<'b, '_, Pg>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl FromSql<Text, Pg> for BackfillStatus {
+    fn from_sql(bytes: PgValue<'_>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, Pg>>::from_sql(bytes)?.as_str() {
+            "in_progress" => Ok(BackfillStatus::InProgress),
+            "complete" => Ok(BackfillStatus::Complete),
+            other => Err(format!("Unrecognized backfill status: {other}").into()),
+        }
+    }
+}
+
+#[derive(AsChangeset, Debug, Insertable)]
+#[diesel(table_name = backfill_processor_status)]
+/// Tracks the progress of a bounded backfill, separately from the live processor
+pub struct BackfillProcessorStatus {
+    pub backfill_id: String,
+    pub backfill_status: BackfillStatus,
+    pub last_success_version: i64,
+    pub last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub backfill_start_version: i64,
+    pub backfill_end_version: i64,
+}
+
+#[derive(Debug, Queryable)]
+#[diesel(table_name = backfill_processor_status)]
+/// Tracks the progress of a bounded backfill, separately from the live processor
+pub struct BackfillProcessorStatusQuery {
+    pub backfill_id: String,
+    pub backfill_status: BackfillStatus,
+    pub last_success_version: i64,
+    pub last_updated: chrono::NaiveDateTime,
+    pub last_transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub backfill_start_version: i64,
+    pub backfill_end_version: i64,
+}
+
+impl BackfillProcessorStatusQuery {
+    pub async fn get_by_backfill_id(
+        backfill_id: &str,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<Option<Self>> {
+        backfill_processor_status::table
+            .filter(backfill_processor_status::backfill_id.eq(backfill_id))
+            .first::<Self>(conn)
+            .await
+            .optional()
+    }
+}
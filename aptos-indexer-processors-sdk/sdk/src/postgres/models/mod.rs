@@ -1,2 +1,3 @@
+pub mod backfill_processor_status;
 pub mod ledger_info;
 pub mod processor_status;
@@ -40,4 +40,14 @@ impl ProcessorStatusQuery {
             .await
             .optional()
     }
+
+    pub async fn get_by_processors(
+        processor_names: Vec<String>,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<Vec<Self>> {
+        processor_status::table
+            .filter(processor_status::processor.eq_any(processor_names))
+            .load::<Self>(conn)
+            .await
+    }
 }
@@ -1,18 +1,26 @@
 //! Postgres-specific progress health checking.
 //!
 //! This module provides `PostgresProgressStatusProvider`, which implements the
-//! `ProgressStatusProvider` trait for postgres-backed processors.
+//! `ProgressStatusProvider` trait for postgres-backed processors, and
+//! `PostgresBackfillProgressStatusProvider`, its counterpart for backfills.
 
 use crate::{
-    health::ProgressStatusProvider,
-    postgres::{models::processor_status::ProcessorStatusQuery, utils::database::ArcDbPool},
+    health::{ProgressStatusProvider, TransactionTimestampProvider},
+    postgres::{
+        models::{
+            backfill_processor_status::BackfillProcessorStatusQuery,
+            processor_status::ProcessorStatusQuery,
+        },
+        utils::database::ArcDbPool,
+    },
 };
 use async_trait::async_trait;
 use chrono::NaiveDateTime;
 
 /// A postgres-backed implementation of `ProgressStatusProvider`.
 ///
-/// This queries the `processor_status` table to get the last updated timestamp.
+/// This queries the `processor_status` table to get the last updated timestamp, and the
+/// last transaction timestamp for `ChainLagHealthChecker`.
 pub struct PostgresProgressStatusProvider {
     processor_name: String,
     db_pool: ArcDbPool,
@@ -43,3 +51,55 @@ impl ProgressStatusProvider for PostgresProgressStatusProvider {
         Ok(status.map(|s| s.last_updated))
     }
 }
+
+#[async_trait]
+impl TransactionTimestampProvider for PostgresProgressStatusProvider {
+    async fn get_last_transaction_timestamp(&self) -> Result<Option<NaiveDateTime>, String> {
+        let mut conn = self
+            .db_pool
+            .get()
+            .await
+            .map_err(|e| format!("Failed to get DB connection: {}", e))?;
+
+        let status = ProcessorStatusQuery::get_by_processor(&self.processor_name, &mut conn)
+            .await
+            .map_err(|e| format!("Failed to query processor status: {}", e))?;
+
+        Ok(status.and_then(|s| s.last_transaction_timestamp))
+    }
+}
+
+/// A postgres-backed implementation of `ProgressStatusProvider` for backfills.
+///
+/// Backfills checkpoint to `backfill_processor_status` instead of `processor_status`, so this
+/// queries the row of `backfill_id` instead.
+pub struct PostgresBackfillProgressStatusProvider {
+    backfill_id: String,
+    db_pool: ArcDbPool,
+}
+
+impl PostgresBackfillProgressStatusProvider {
+    pub fn new(backfill_id: String, db_pool: ArcDbPool) -> Self {
+        Self {
+            backfill_id,
+            db_pool,
+        }
+    }
+}
+
+#[async_trait]
+impl ProgressStatusProvider for PostgresBackfillProgressStatusProvider {
+    async fn get_last_updated(&self) -> Result<Option<NaiveDateTime>, String> {
+        let mut conn = self
+            .db_pool
+            .get()
+            .await
+            .map_err(|e| format!("Failed to get DB connection: {}", e))?;
+
+        let status = BackfillProcessorStatusQuery::get_by_backfill_id(&self.backfill_id, &mut conn)
+            .await
+            .map_err(|e| format!("Failed to query backfill status: {}", e))?;
+
+        Ok(status.map(|s| s.last_updated))
+    }
+}
@@ -0,0 +1,34 @@
+use crate::common_steps::ShardingConfig;
+use serde::{Deserialize, Serialize};
+
+/// Configuration for running a bounded backfill. Progress is tracked in
+/// `backfill_processor_status` under `backfill_id`, so the live processor's
+/// `processor_status` row is left untouched.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackfillConfig {
+    /// Identifies the backfill. Restarting with the same id resumes from its last checkpoint.
+    /// With sharding, each shard uses `backfill_id#shard-i` instead.
+    pub backfill_id: String,
+    pub backfill_start_version: u64,
+    /// Inclusive. The backfill is marked complete once this version has been processed.
+    pub backfill_end_version: u64,
+    /// If true, restart from `backfill_start_version` even if the backfill already has
+    /// progress, including if it was already complete.
+    #[serde(default)]
+    pub overwrite_checkpoint: bool,
+}
+
+impl BackfillConfig {
+    /// Returns the config of this shard's part of the backfill. Shards make progress
+    /// independently, so each tracks its checkpoint under its own `backfill_id#shard-i`.
+    pub fn for_shard(self, sharding_config: Option<&ShardingConfig>) -> Self {
+        match sharding_config {
+            Some(sharding_config) => Self {
+                backfill_id: sharding_config.shard_processor_name(&self.backfill_id),
+                ..self
+            },
+            None => self,
+        }
+    }
+}
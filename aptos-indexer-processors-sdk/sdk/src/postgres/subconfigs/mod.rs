@@ -1 +1,2 @@
+pub mod backfill_config;
 pub mod postgres_config;
@@ -0,0 +1,23 @@
+//! Helpers for tests that need a real Postgres database, started with `PostgresTestDatabase`.
+
+use crate::{
+    postgres::{
+        utils::database::{new_db_pool, run_migrations, ArcDbPool},
+        SDK_MIGRATIONS,
+    },
+    testing_framework::database::{PostgresTestDatabase, TestDatabase},
+};
+
+/// Starts a Postgres container with the SDK migrations applied. The container is stopped when
+/// the returned `PostgresTestDatabase` is dropped, so keep it alive for the whole test.
+pub(crate) async fn setup_test_db() -> (PostgresTestDatabase, ArcDbPool) {
+    let mut db = PostgresTestDatabase::new();
+    db.setup()
+        .await
+        .expect("Failed to start Postgres container");
+    let db_pool = new_db_pool(&db.get_db_url(), Some(5))
+        .await
+        .expect("Failed to create connection pool");
+    run_migrations(db.get_db_url(), db_pool.clone(), SDK_MIGRATIONS).await;
+    (db, db_pool)
+}
@@ -0,0 +1,305 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Bulk loading through `COPY ... FROM STDIN (FORMAT binary)`.
+//!
+//! `execute_in_chunks` sends parameterised INSERTs, so every statement is capped by
+//! `MAX_DIESEL_PARAM_SIZE`. For backfills it is much faster to stream all rows into a
+//! temporary staging table with `COPY` and then merge them into the target table with a
+//! single `INSERT ... SELECT ... ON CONFLICT`.
+//!
+//! The staging table is created with the same (unqualified) name as the target table.
+//! Postgres searches `pg_temp` before any other schema, so the `COPY` statement that
+//! diesel generates for the target table lands in the staging table instead. This means
+//! the target table must be addressable without a schema prefix, i.e. it must live in a
+//! schema on the `search_path`. The target is looked up in those schemas explicitly, so the
+//! staging table never stands in for it, and the staging table is dropped after the merge,
+//! so that the next load within the same transaction can create it again.
+//!
+//! Only the columns of the target table that can be written are merged, so generated
+//! columns and `GENERATED ALWAYS` identity columns are computed by Postgres.
+
+use super::database::clean_data_for_db;
+use crate::utils::errors::ProcessorError;
+use diesel::{
+    prelude::ExecuteCopyFromDsl, sql_query, sql_types::Text, Connection, OptionalExtension,
+    PgConnection, QueryableByName, RunQueryDsl,
+};
+use tracing::warn;
+
+/// What to do when a staged row conflicts with an existing row in the target table.
+#[derive(Clone, Debug)]
+pub enum ConflictPolicy {
+    /// Keep the existing row and drop the staged one.
+    DoNothing,
+    /// Overwrite the given columns with the staged values.
+    DoUpdate { columns: Vec<String> },
+    /// Overwrite the given columns with the staged values, but only if `condition` holds.
+    /// The condition can refer to the staged row through `EXCLUDED`, e.g.
+    /// `events.transaction_version <= EXCLUDED.transaction_version`.
+    DoUpdateWhere {
+        columns: Vec<String>,
+        condition: String,
+    },
+}
+
+/// Describes how staged rows are merged into the target table.
+#[derive(Clone, Debug)]
+pub struct CopyMergeConfig {
+    /// Unqualified name of the target table, e.g. `events`.
+    pub table_name: String,
+    /// Columns of the unique constraint used for `ON CONFLICT`.
+    pub conflict_columns: Vec<String>,
+    pub conflict_policy: ConflictPolicy,
+}
+
+impl CopyMergeConfig {
+    pub fn new(
+        table_name: &str,
+        conflict_columns: &[&str],
+        conflict_policy: ConflictPolicy,
+    ) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            conflict_columns: conflict_columns.iter().map(|c| c.to_string()).collect(),
+            conflict_policy,
+        }
+    }
+
+    fn create_staging_table_query(&self, qualified_target: &str) -> String {
+        format!(
+            "CREATE TEMP TABLE {} (LIKE {} INCLUDING DEFAULTS) ON COMMIT DROP",
+            quote_ident(&self.table_name),
+            qualified_target,
+        )
+    }
+
+    /// Builds the statement that moves the `columns` of the staged rows into the target
+    /// table. Staged rows are deduplicated on the conflict columns first, keeping the last
+    /// row written, since Postgres refuses to update the same row twice in a single `INSERT`.
+    fn merge_query(&self, qualified_target: &str, columns: &[String]) -> String {
+        let columns = columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let conflict_columns = self
+            .conflict_columns
+            .iter()
+            .map(|c| quote_ident(c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let on_conflict = match &self.conflict_policy {
+            ConflictPolicy::DoNothing => "DO NOTHING".to_string(),
+            ConflictPolicy::DoUpdate { columns } => {
+                format!("DO UPDATE SET {}", update_assignments(columns))
+            },
+            ConflictPolicy::DoUpdateWhere { columns, condition } => format!(
+                "DO UPDATE SET {} WHERE {}",
+                update_assignments(columns),
+                condition
+            ),
+        };
+        format!(
+            "INSERT INTO {qualified_target} ({columns}) SELECT {columns} FROM (SELECT DISTINCT ON ({conflict_columns}) {columns} FROM pg_temp.{staging} ORDER BY {conflict_columns}, ctid DESC) AS staged ON CONFLICT ({conflict_columns}) {on_conflict}",
+            staging = quote_ident(&self.table_name),
+        )
+    }
+
+    fn drop_staging_table_query(&self) -> String {
+        format!("DROP TABLE pg_temp.{}", quote_ident(&self.table_name))
+    }
+}
+
+#[derive(QueryableByName)]
+struct QualifiedTableName {
+    #[diesel(sql_type = Text)]
+    qualified_name: String,
+}
+
+#[derive(QueryableByName)]
+struct ColumnName {
+    #[diesel(sql_type = Text)]
+    column_name: String,
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn update_assignments(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|c| format!("{0} = EXCLUDED.{0}", quote_ident(c)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Streams `items_to_insert` into a staging table with binary `COPY` and merges them into
+/// the target table according to `merge_config`. Returns the number of rows written to the
+/// target table.
+///
+/// `build_copy_query` must build the `COPY` for the target table, typically
+/// `|items| diesel::copy_from(events::table).from_insertable(items)`. If the load fails,
+/// it is retried once with null bytes removed from the items, like `execute_in_chunks`.
+///
+/// This uses a dedicated synchronous `PgConnection`, hence it requires the `postgres_full`
+/// feature.
+pub async fn copy_in_and_merge<T, Q>(
+    postgres_connection_string: String,
+    build_copy_query: fn(Vec<T>) -> Q,
+    items_to_insert: Vec<T>,
+    merge_config: CopyMergeConfig,
+) -> Result<usize, ProcessorError>
+where
+    Q: ExecuteCopyFromDsl<PgConnection, Error = diesel::result::Error>,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    if items_to_insert.is_empty() {
+        return Ok(0);
+    }
+    if merge_config.table_name.contains('.') {
+        return Err(ProcessorError::DBStoreError {
+            message: format!(
+                "Bulk loading requires an unqualified table name, got {}",
+                merge_config.table_name
+            ),
+            query: None,
+        });
+    }
+
+    // We use spawn_blocking since COPY is only supported on the synchronous connection.
+    tokio::task::spawn_blocking(move || {
+        let mut conn = PgConnection::establish(&postgres_connection_string).map_err(|e| {
+            ProcessorError::DBStoreError {
+                message: format!("{e:#}"),
+                query: None,
+            }
+        })?;
+        match copy_in_and_merge_with_conn(
+            &mut conn,
+            build_copy_query,
+            items_to_insert.clone(),
+            &merge_config,
+        ) {
+            Ok(num_rows) => Ok(num_rows),
+            Err(_) => {
+                let cleaned_items = clean_data_for_db(items_to_insert, true);
+                copy_in_and_merge_with_conn(
+                    &mut conn,
+                    build_copy_query,
+                    cleaned_items,
+                    &merge_config,
+                )
+            },
+        }
+    })
+    .await
+    .expect("Task panicked bulk loading rows")
+}
+
+/// Same as `copy_in_and_merge`, but on a connection the caller already holds and without
+/// the null byte retry. Everything runs in a single transaction, so a failure leaves the
+/// target table untouched.
+pub fn copy_in_and_merge_with_conn<T, Q>(
+    conn: &mut PgConnection,
+    build_copy_query: fn(Vec<T>) -> Q,
+    items_to_insert: Vec<T>,
+    merge_config: &CopyMergeConfig,
+) -> Result<usize, ProcessorError>
+where
+    Q: ExecuteCopyFromDsl<PgConnection, Error = diesel::result::Error>,
+{
+    let to_db_store_error = |e: diesel::result::Error| ProcessorError::DBStoreError {
+        message: format!("{e:#}"),
+        query: None,
+    };
+    // The first table of that name on the search path, leaving out `pg_temp`, where a staging
+    // table of the caller's transaction may still shadow it.
+    let qualified_target = sql_query(
+        "SELECT quote_ident(n.nspname) || '.' || quote_ident(c.relname) AS qualified_name \
+         FROM pg_class c \
+         JOIN pg_namespace n ON n.oid = c.relnamespace \
+         JOIN unnest(current_schemas(false)) WITH ORDINALITY AS s(nspname, position) \
+         ON s.nspname = n.nspname \
+         WHERE c.relname = $1 AND c.relkind IN ('r', 'p') \
+         ORDER BY s.position LIMIT 1",
+    )
+    .bind::<Text, _>(&merge_config.table_name)
+    .get_result::<QualifiedTableName>(conn)
+    .optional()
+    .map_err(to_db_store_error)?
+    .ok_or_else(|| ProcessorError::DBStoreError {
+        message: format!("Table {} does not exist", merge_config.table_name),
+        query: None,
+    })?
+    .qualified_name;
+    let columns = sql_query(
+        "SELECT a.attname::text AS column_name FROM pg_attribute a \
+         WHERE a.attrelid = to_regclass($1) AND a.attnum > 0 AND NOT a.attisdropped \
+         AND a.attgenerated = '' AND a.attidentity <> 'a' \
+         ORDER BY a.attnum",
+    )
+    .bind::<Text, _>(&qualified_target)
+    .load::<ColumnName>(conn)
+    .map_err(to_db_store_error)?
+    .into_iter()
+    .map(|column| column.column_name)
+    .collect::<Vec<_>>();
+
+    let create_staging_table = merge_config.create_staging_table_query(&qualified_target);
+    let merge = merge_config.merge_query(&qualified_target, &columns);
+    let drop_staging_table = merge_config.drop_staging_table_query();
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        RunQueryDsl::execute(sql_query(&create_staging_table), conn)?;
+        ExecuteCopyFromDsl::execute(build_copy_query(items_to_insert), conn)?;
+        let num_rows = RunQueryDsl::execute(sql_query(&merge), conn)?;
+        RunQueryDsl::execute(sql_query(&drop_staging_table), conn)?;
+        Ok(num_rows)
+    })
+    .map_err(|e| {
+        warn!(
+            table_name = merge_config.table_name,
+            error = ?e,
+            "Error bulk loading rows"
+        );
+        ProcessorError::DBStoreError {
+            message: format!("{e:#}"),
+            query: Some(merge.clone()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_query() {
+        let config = CopyMergeConfig::new(
+            "events",
+            &["transaction_version", "event_index"],
+            ConflictPolicy::DoUpdateWhere {
+                columns: vec!["data".to_string()],
+                condition: "events.transaction_version <= EXCLUDED.transaction_version".to_string(),
+            },
+        );
+        assert_eq!(
+            config.create_staging_table_query("\"public\".\"events\""),
+            "CREATE TEMP TABLE \"events\" (LIKE \"public\".\"events\" INCLUDING DEFAULTS) ON COMMIT DROP"
+        );
+        assert_eq!(
+            config.drop_staging_table_query(),
+            "DROP TABLE pg_temp.\"events\""
+        );
+        let columns = ["transaction_version", "event_index", "data"].map(str::to_string);
+        assert_eq!(
+            config.merge_query("\"public\".\"events\"", &columns),
+            "INSERT INTO \"public\".\"events\" (\"transaction_version\", \"event_index\", \"data\") \
+             SELECT \"transaction_version\", \"event_index\", \"data\" FROM (SELECT DISTINCT ON (\"transaction_version\", \"event_index\") \
+             \"transaction_version\", \"event_index\", \"data\" FROM pg_temp.\"events\" ORDER BY \"transaction_version\", \"event_index\", ctid DESC) AS staged \
+             ON CONFLICT (\"transaction_version\", \"event_index\") \
+             DO UPDATE SET \"data\" = EXCLUDED.\"data\" WHERE events.transaction_version <= EXCLUDED.transaction_version"
+        );
+    }
+}
@@ -1,13 +1,19 @@
 use super::database::{execute_with_better_error, execute_with_better_error_conn, ArcDbPool};
 use crate::{
     aptos_indexer_transaction_stream::{utils::time::parse_timestamp, TransactionStreamConfig},
-    common_steps::ProcessorStatusSaver,
+    common_steps::{shard_filter_step::shard_processor_name, ProcessorStatusSaver},
     postgres::{
         models::{
+            backfill_processor_status::{
+                BackfillProcessorStatus, BackfillProcessorStatusQuery, BackfillStatus,
+            },
             ledger_info::LedgerInfo,
             processor_status::{ProcessorStatus, ProcessorStatusQuery},
         },
-        processor_metadata_schema::processor_metadata::{ledger_infos, processor_status},
+        processor_metadata_schema::processor_metadata::{
+            backfill_processor_status, ledger_infos, processor_status,
+        },
+        subconfigs::backfill_config::BackfillConfig,
     },
     types::transaction_context::TransactionContext,
     utils::{chain_id_check::ChainIdChecker, errors::ProcessorError},
@@ -115,6 +121,100 @@ impl ProcessorStatusSaver for PostgresProcessorStatusSaver {
     }
 }
 
+/// A trait implementation of ProcessorStatusSaver for backfills, which writes to
+/// `backfill_processor_status` instead of `processor_status`.
+pub struct PostgresBackfillStatusSaver {
+    pub db_pool: ArcDbPool,
+    pub backfill_config: BackfillConfig,
+}
+
+impl PostgresBackfillStatusSaver {
+    pub fn new(backfill_config: BackfillConfig, db_pool: ArcDbPool) -> Self {
+        Self {
+            db_pool,
+            backfill_config,
+        }
+    }
+}
+
+#[async_trait]
+impl ProcessorStatusSaver for PostgresBackfillStatusSaver {
+    async fn save_processor_status(
+        &self,
+        last_success_batch: &TransactionContext<()>,
+    ) -> Result<(), ProcessorError> {
+        let end_version = last_success_batch.metadata.end_version;
+        let last_transaction_timestamp = last_success_batch
+            .metadata
+            .end_transaction_timestamp
+            .as_ref()
+            .map(|t| parse_timestamp(t, end_version as i64))
+            .map(|t| t.naive_utc());
+        let backfill_status = if end_version >= self.backfill_config.backfill_end_version {
+            BackfillStatus::Complete
+        } else {
+            BackfillStatus::InProgress
+        };
+        let status = BackfillProcessorStatus {
+            backfill_id: self.backfill_config.backfill_id.clone(),
+            backfill_status,
+            last_success_version: end_version as i64,
+            last_transaction_timestamp,
+            backfill_start_version: self.backfill_config.backfill_start_version as i64,
+            backfill_end_version: self.backfill_config.backfill_end_version as i64,
+        };
+
+        execute_with_better_error(
+            self.db_pool.clone(),
+            diesel::insert_into(backfill_processor_status::table)
+                .values(&status)
+                .on_conflict(backfill_processor_status::backfill_id)
+                .do_update()
+                .set((
+                    backfill_processor_status::backfill_status
+                        .eq(excluded(backfill_processor_status::backfill_status)),
+                    backfill_processor_status::last_success_version
+                        .eq(excluded(backfill_processor_status::last_success_version)),
+                    backfill_processor_status::last_updated
+                        .eq(excluded(backfill_processor_status::last_updated)),
+                    backfill_processor_status::last_transaction_timestamp.eq(excluded(
+                        backfill_processor_status::last_transaction_timestamp,
+                    )),
+                    backfill_processor_status::backfill_start_version
+                        .eq(excluded(backfill_processor_status::backfill_start_version)),
+                    backfill_processor_status::backfill_end_version
+                        .eq(excluded(backfill_processor_status::backfill_end_version)),
+                )),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Saves progress either as a regular processor or as a backfill, so that the pipeline has a
+/// single type regardless of the mode.
+pub enum PostgresStatusSaver {
+    Processor(PostgresProcessorStatusSaver),
+    Backfill(PostgresBackfillStatusSaver),
+}
+
+#[async_trait]
+impl ProcessorStatusSaver for PostgresStatusSaver {
+    async fn save_processor_status(
+        &self,
+        last_success_batch: &TransactionContext<()>,
+    ) -> Result<(), ProcessorError> {
+        match self {
+            PostgresStatusSaver::Processor(saver) => {
+                saver.save_processor_status(last_success_batch).await
+            },
+            PostgresStatusSaver::Backfill(saver) => {
+                saver.save_processor_status(last_success_batch).await
+            },
+        }
+    }
+}
+
 pub async fn get_starting_version(
     processor_name: &str,
     transaction_stream_config: TransactionStreamConfig,
@@ -128,3 +228,145 @@ pub async fn get_starting_version(
     // If nothing checkpointed, return the `starting_version` from the config, or 0 if not set.
     Ok(latest_processed_version.unwrap_or(transaction_stream_config.starting_version.unwrap_or(0)))
 }
+
+/// Returns the version up to which every shard of a sharded processor has processed data,
+/// i.e. the minimum `last_success_version` across all `processor_name#shard-i` rows.
+/// Returns `None` until every shard has checkpointed at least once.
+pub async fn get_global_safe_version(
+    processor_name: &str,
+    num_shards: u64,
+    conn_pool: ArcDbPool,
+) -> Result<Option<u64>> {
+    let mut conn = conn_pool.get().await?;
+    let shard_names = (0..num_shards)
+        .map(|shard_index| shard_processor_name(processor_name, shard_index))
+        .collect::<Vec<_>>();
+    let statuses = ProcessorStatusQuery::get_by_processors(shard_names, &mut conn).await?;
+    if statuses.len() as u64 != num_shards {
+        return Ok(None);
+    }
+    Ok(statuses
+        .iter()
+        .map(|ps| ps.last_success_version as u64)
+        .min())
+}
+
+/// Returns the version a backfill should start (or resume) from, or `None` if the backfill
+/// is already complete and there is nothing left to do.
+pub async fn get_backfill_starting_version(
+    backfill_config: &BackfillConfig,
+    conn_pool: ArcDbPool,
+) -> Result<Option<u64>> {
+    if backfill_config.overwrite_checkpoint {
+        return Ok(Some(backfill_config.backfill_start_version));
+    }
+    let mut conn = conn_pool.get().await?;
+    let status =
+        BackfillProcessorStatusQuery::get_by_backfill_id(&backfill_config.backfill_id, &mut conn)
+            .await?;
+    Ok(match status {
+        // A completed backfill whose range was extended in the config resumes instead.
+        Some(status)
+            if status.backfill_status == BackfillStatus::Complete
+                && status.backfill_end_version as u64 >= backfill_config.backfill_end_version =>
+        {
+            None
+        },
+        Some(status) => Some(status.last_success_version as u64),
+        None => Some(backfill_config.backfill_start_version),
+    })
+}
+
+#[cfg(all(test, feature = "testing_framework"))]
+mod tests {
+    use super::*;
+    use crate::{
+        postgres::test_utils::setup_test_db, types::transaction_context::TransactionMetadata,
+    };
+
+    fn batch(end_version: u64) -> TransactionContext<()> {
+        TransactionContext {
+            data: (),
+            metadata: TransactionMetadata {
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    fn backfill_config(backfill_end_version: u64) -> BackfillConfig {
+        BackfillConfig {
+            backfill_id: "test_backfill".to_string(),
+            backfill_start_version: 10,
+            backfill_end_version,
+            overwrite_checkpoint: false,
+        }
+    }
+
+    async fn backfill_status(db_pool: &ArcDbPool) -> BackfillProcessorStatusQuery {
+        let mut conn = db_pool.get().await.unwrap();
+        BackfillProcessorStatusQuery::get_by_backfill_id("test_backfill", &mut conn)
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_backfill_checkpoint_and_resume() {
+        let (_db, db_pool) = setup_test_db().await;
+        let config = backfill_config(100);
+
+        // Nothing checkpointed yet, so start from the start of the range.
+        let starting_version = get_backfill_starting_version(&config, db_pool.clone())
+            .await
+            .unwrap();
+        assert_eq!(starting_version, Some(10));
+
+        let saver = PostgresBackfillStatusSaver::new(config.clone(), db_pool.clone());
+        saver.save_processor_status(&batch(50)).await.unwrap();
+        let status = backfill_status(&db_pool).await;
+        assert_eq!(status.backfill_status, BackfillStatus::InProgress);
+        assert_eq!(status.last_success_version, 50);
+        let starting_version = get_backfill_starting_version(&config, db_pool.clone())
+            .await
+            .unwrap();
+        assert_eq!(starting_version, Some(50));
+
+        // Overwriting the checkpoint restarts from the start of the range.
+        let starting_version = get_backfill_starting_version(
+            &BackfillConfig {
+                overwrite_checkpoint: true,
+                ..config
+            },
+            db_pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(starting_version, Some(10));
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_backfill_completion() {
+        let (_db, db_pool) = setup_test_db().await;
+        let config = backfill_config(100);
+        let saver = PostgresBackfillStatusSaver::new(config.clone(), db_pool.clone());
+
+        // Reaching the end version completes the backfill, so there is nothing left to do.
+        saver.save_processor_status(&batch(100)).await.unwrap();
+        let status = backfill_status(&db_pool).await;
+        assert_eq!(status.backfill_status, BackfillStatus::Complete);
+        assert_eq!(status.last_success_version, 100);
+        let starting_version = get_backfill_starting_version(&config, db_pool.clone())
+            .await
+            .unwrap();
+        assert_eq!(starting_version, None);
+
+        // Extending the range of a complete backfill resumes it.
+        let starting_version = get_backfill_starting_version(&backfill_config(200), db_pool)
+            .await
+            .unwrap();
+        assert_eq!(starting_version, Some(100));
+    }
+}
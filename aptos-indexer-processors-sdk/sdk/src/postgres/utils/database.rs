@@ -4,7 +4,10 @@
 //! Database-related functions
 #![allow(clippy::extra_unused_lifetimes)]
 
-use crate::utils::{convert::remove_null_bytes, errors::ProcessorError};
+use crate::{
+    postgres::SDK_MIGRATIONS,
+    utils::{convert::remove_null_bytes, errors::ProcessorError},
+};
 use ahash::AHashMap;
 use diesel::{query_builder::QueryFragment, ConnectionResult, QueryResult};
 use diesel_async::{
@@ -28,6 +31,9 @@ pub type DbPoolConnection<'a> = PooledConnection<'a, MyDbConnection>;
 
 pub const DEFAULT_MAX_POOL_SIZE: u32 = 150;
 
+/// Key of the advisory lock held while running migrations.
+pub const MIGRATIONS_ADVISORY_LOCK_ID: i64 = 7_301_554_211_962_410;
+
 // the max is actually u16::MAX but we see that when the size is too big we get an overflow error so reducing it a bit
 pub const MAX_DIESEL_PARAM_SIZE: usize = (u16::MAX / 2) as usize;
 
@@ -231,14 +237,60 @@ pub fn run_pending_migrations<DB: diesel::backend::Backend>(
         .expect("[Parser] Migrations failed!");
 }
 
+/// Runs each set of migrations in order while holding a session-level advisory lock, so
+/// that replicas starting at the same time don't race on the same migrations. Replicas
+/// that don't get the lock wait for it and then find nothing left to run.
+pub fn run_pending_migrations_with_lock<C>(conn: &mut C, migrations: Vec<EmbeddedMigrations>)
+where
+    C: MigrationHarness<Backend> + diesel::Connection<Backend = Backend>,
+{
+    diesel::RunQueryDsl::execute(
+        diesel::sql_query("SELECT pg_advisory_lock($1)")
+            .bind::<diesel::sql_types::BigInt, _>(MIGRATIONS_ADVISORY_LOCK_ID),
+        conn,
+    )
+    .expect("[Parser] Failed to acquire migrations lock");
+    for migrations in migrations {
+        run_pending_migrations(conn, migrations);
+    }
+    diesel::RunQueryDsl::execute(
+        diesel::sql_query("SELECT pg_advisory_unlock($1)")
+            .bind::<diesel::sql_types::BigInt, _>(MIGRATIONS_ADVISORY_LOCK_ID),
+        conn,
+    )
+    .expect("[Parser] Failed to release migrations lock");
+}
+
+/// Runs the user's migrations followed by the SDK migrations (`SDK_MIGRATIONS`) under a
+/// single migrations lock.
+pub async fn run_user_and_sdk_migrations(
+    postgres_connection_string: String,
+    conn_pool: ArcDbPool,
+    migrations: EmbeddedMigrations,
+) {
+    run_migration_sets(postgres_connection_string, conn_pool, vec![
+        migrations,
+        SDK_MIGRATIONS,
+    ])
+    .await;
+}
+
+pub async fn run_migrations(
+    postgres_connection_string: String,
+    conn_pool: ArcDbPool,
+    migrations: EmbeddedMigrations,
+) {
+    run_migration_sets(postgres_connection_string, conn_pool, vec![migrations]).await;
+}
+
 // For the normal processor build we just use standard Diesel with the postgres
 // feature enabled (which uses libpq under the hood, hence why we named the feature
 // this way).
 #[cfg(feature = "postgres_full")]
-pub async fn run_migrations(
+async fn run_migration_sets(
     postgres_connection_string: String,
     _conn_pool: ArcDbPool,
-    migrations: EmbeddedMigrations,
+    migrations: Vec<EmbeddedMigrations>,
 ) {
     use diesel::{Connection, PgConnection};
 
@@ -246,7 +298,7 @@ pub async fn run_migrations(
     let migration_time = std::time::Instant::now();
     let mut conn =
         PgConnection::establish(&postgres_connection_string).expect("migrations failed!");
-    run_pending_migrations(&mut conn, migrations);
+    run_pending_migrations_with_lock(&mut conn, migrations);
     info!(
         duration_in_secs = migration_time.elapsed().as_secs_f64(),
         "[Parser] Finished migrations"
@@ -256,14 +308,15 @@ pub async fn run_migrations(
 // If the postgres_full feature isn't enabled, we use diesel async instead. This is used by
 // the CLI for the local testnet, where we cannot tolerate the libpq dependency.
 #[cfg(not(feature = "postgres_full"))]
-pub async fn run_migrations(
+async fn run_migration_sets(
     postgres_connection_string: String,
     conn_pool: ArcDbPool,
-    migrations: EmbeddedMigrations,
+    migrations: Vec<EmbeddedMigrations>,
 ) {
     use diesel_async::async_connection_wrapper::AsyncConnectionWrapper;
 
     info!("Running migrations: {:?}", postgres_connection_string);
+    let migration_time = std::time::Instant::now();
     let conn = conn_pool
         // We need to use this since AsyncConnectionWrapper doesn't know how to
         // work with a pooled connection.
@@ -276,10 +329,14 @@ pub async fn run_migrations(
         // https://docs.rs/diesel-async/latest/diesel_async/async_connection_wrapper/type.AsyncConnectionWrapper.html
         let mut conn: AsyncConnectionWrapper<diesel_async::AsyncPgConnection> =
             AsyncConnectionWrapper::from(conn);
-        run_pending_migrations(&mut conn, migrations);
+        run_pending_migrations_with_lock(&mut conn, migrations);
     })
     .await
     .expect("[Parser] Failed to run migrations");
+    info!(
+        duration_in_secs = migration_time.elapsed().as_secs_f64(),
+        "[Parser] Finished migrations"
+    );
 }
 
 pub struct DbContext<'a> {
@@ -1,2 +1,5 @@
+#[cfg(feature = "postgres_full")]
+pub mod bulk_load;
 pub mod checkpoint;
 pub mod database;
+pub mod rewind;
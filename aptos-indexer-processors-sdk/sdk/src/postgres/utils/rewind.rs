@@ -0,0 +1,436 @@
+//! Rewinding a processor to an earlier version, e.g. after shipping a buggy parser.
+//!
+//! A rewind sets the processor's `last_success_version` back to a target version and runs
+//! the registered `RewindHook`s, which remove the rows derived from later versions. Both
+//! happen in a single transaction, so the processor either restarts from a consistent state
+//! or nothing changes. The processor must be stopped while rewinding, otherwise it would
+//! checkpoint over the rewound status.
+
+use super::database::{ArcDbPool, MyDbConnection};
+use crate::{
+    postgres::{
+        models::processor_status::ProcessorStatusQuery,
+        processor_metadata_schema::processor_metadata::processor_status,
+    },
+    server_framework::RewindArgs,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use diesel::{sql_types::BigInt, ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use tracing::info;
+
+/// Removes the data a processor derived from versions after the rewind target.
+#[async_trait]
+pub trait RewindHook: Send + Sync {
+    /// Name of the table the hook cleans up, used for logging.
+    fn table_name(&self) -> &str;
+
+    /// Removes (or reverts) the rows derived from versions after `target_version` and returns
+    /// the number of affected rows. Runs inside the rewind transaction.
+    async fn rewind(&self, conn: &mut MyDbConnection, target_version: u64) -> Result<usize>;
+}
+
+/// Deletes every row of `table_name` whose `version_column` is after the rewind target, i.e.
+/// `DELETE FROM table_name WHERE version_column > target_version`.
+///
+/// The table and column names are inserted in the query as is, so they must come from the
+/// processor's code, not from user input.
+pub struct DeleteAfterVersion {
+    table_name: String,
+    version_column: String,
+}
+
+impl DeleteAfterVersion {
+    pub fn new(table_name: &str, version_column: &str) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            version_column: version_column.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl RewindHook for DeleteAfterVersion {
+    fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    async fn rewind(&self, conn: &mut MyDbConnection, target_version: u64) -> Result<usize> {
+        diesel::sql_query(format!(
+            "DELETE FROM {} WHERE {} > $1",
+            self.table_name, self.version_column
+        ))
+        .bind::<BigInt, _>(target_version as i64)
+        .execute(conn)
+        .await
+        .with_context(|| format!("Error deleting rows from {}", self.table_name))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RewindOptions {
+    pub target_version: u64,
+    /// Refuse to rewind if the processor updated its status less than this many seconds
+    /// ago, since it is then most likely still running.
+    pub min_idle_secs: u64,
+    /// Skip the `min_idle_secs` check.
+    pub force: bool,
+}
+
+impl From<&RewindArgs> for RewindOptions {
+    fn from(rewind_args: &RewindArgs) -> Self {
+        Self {
+            target_version: rewind_args.to_version,
+            min_idle_secs: rewind_args.min_idle_secs,
+            force: rewind_args.force,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RewindSummary {
+    pub previous_version: u64,
+    pub target_version: u64,
+    /// Number of rows affected by each hook, by table name.
+    pub rows_affected: Vec<(String, usize)>,
+}
+
+/// Rewinds `processor_name` to `options.target_version`. See the module documentation for
+/// details. When it restarts, the processor resumes from the target version.
+pub async fn rewind_processor(
+    processor_name: &str,
+    options: RewindOptions,
+    hooks: &[Box<dyn RewindHook>],
+    db_pool: ArcDbPool,
+) -> Result<RewindSummary> {
+    let mut conn = db_pool.get().await.context("Error getting db connection")?;
+    let summary = conn
+        .transaction::<_, anyhow::Error, _>(|conn| {
+            async move {
+                // Lock the row so that a processor that is still running blocks on its next
+                // checkpoint instead of racing with the rewind.
+                let status = processor_status::table
+                    .filter(processor_status::processor.eq(processor_name))
+                    .for_update()
+                    .first::<ProcessorStatusQuery>(conn)
+                    .await
+                    .optional()
+                    .context("Error reading processor status")?
+                    .with_context(|| {
+                        format!("No processor status found for {processor_name}, nothing to rewind")
+                    })?;
+
+                let previous_version = status.last_success_version as u64;
+                if options.target_version > previous_version {
+                    bail!(
+                        "Cannot rewind {processor_name} forward: target version {} is after last success version {previous_version}",
+                        options.target_version
+                    );
+                }
+                let idle_secs = (Utc::now().naive_utc() - status.last_updated).num_seconds();
+                if !options.force && idle_secs < options.min_idle_secs as i64 {
+                    bail!(
+                        "{processor_name} updated its status {idle_secs}s ago and may still be running. Stop it and retry after {}s, or force the rewind",
+                        options.min_idle_secs
+                    );
+                }
+
+                let mut rows_affected = Vec::with_capacity(hooks.len());
+                for hook in hooks {
+                    let rows = hook.rewind(conn, options.target_version).await?;
+                    info!(
+                        processor = processor_name,
+                        table_name = hook.table_name(),
+                        rows,
+                        "Rewind hook completed"
+                    );
+                    rows_affected.push((hook.table_name().to_string(), rows));
+                }
+
+                diesel::update(
+                    processor_status::table.filter(processor_status::processor.eq(processor_name)),
+                )
+                .set((
+                    processor_status::last_success_version.eq(options.target_version as i64),
+                    processor_status::last_updated.eq(diesel::dsl::now),
+                    // The timestamp of the target version isn't known until it is reprocessed.
+                    processor_status::last_transaction_timestamp
+                        .eq(None::<chrono::NaiveDateTime>),
+                ))
+                .execute(conn)
+                .await
+                .context("Error updating processor status")?;
+
+                Ok(RewindSummary {
+                    previous_version,
+                    target_version: options.target_version,
+                    rows_affected,
+                })
+            }
+            .scope_boxed()
+        })
+        .await?;
+    info!(
+        processor = processor_name,
+        previous_version = summary.previous_version,
+        target_version = summary.target_version,
+        "Rewound processor status"
+    );
+    Ok(summary)
+}
+
+#[cfg(all(test, feature = "testing_framework"))]
+mod tests {
+    use super::*;
+    use crate::postgres::test_utils::setup_test_db;
+    use diesel::QueryableByName;
+    use std::sync::{Arc, Mutex};
+
+    const PROCESSOR_NAME: &str = "test_processor";
+
+    #[derive(QueryableByName)]
+    struct EventVersion {
+        #[diesel(sql_type = BigInt)]
+        transaction_version: i64,
+    }
+
+    /// Creates an `events` table with one row for each of versions 0 to 9, and a status at
+    /// version 9 last updated `idle_secs` ago.
+    async fn setup_processor(db_pool: &ArcDbPool, idle_secs: i64) {
+        let mut conn = db_pool.get().await.unwrap();
+        diesel::sql_query("CREATE TABLE events (transaction_version BIGINT NOT NULL)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        diesel::sql_query("INSERT INTO events SELECT generate_series(0, 9)")
+            .execute(&mut conn)
+            .await
+            .unwrap();
+        diesel::insert_into(processor_status::table)
+            .values((
+                processor_status::processor.eq(PROCESSOR_NAME),
+                processor_status::last_success_version.eq(9),
+                processor_status::last_updated
+                    .eq(Utc::now().naive_utc() - chrono::Duration::seconds(idle_secs)),
+            ))
+            .execute(&mut conn)
+            .await
+            .unwrap();
+    }
+
+    async fn event_versions(db_pool: &ArcDbPool) -> Vec<i64> {
+        let mut conn = db_pool.get().await.unwrap();
+        diesel::sql_query("SELECT transaction_version FROM events ORDER BY transaction_version")
+            .load::<EventVersion>(&mut conn)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.transaction_version)
+            .collect()
+    }
+
+    async fn last_success_version(db_pool: &ArcDbPool) -> i64 {
+        let mut conn = db_pool.get().await.unwrap();
+        ProcessorStatusQuery::get_by_processor(PROCESSOR_NAME, &mut conn)
+            .await
+            .unwrap()
+            .unwrap()
+            .last_success_version
+    }
+
+    fn options(target_version: u64, force: bool) -> RewindOptions {
+        RewindOptions {
+            target_version,
+            min_idle_secs: 60,
+            force,
+        }
+    }
+
+    fn delete_events() -> Box<dyn RewindHook> {
+        Box::new(DeleteAfterVersion::new("events", "transaction_version"))
+    }
+
+    /// Records the processor's `last_success_version` as seen from inside the transaction.
+    struct RecordingHook {
+        table_name: String,
+        log: Arc<Mutex<Vec<(String, i64)>>>,
+    }
+
+    #[async_trait]
+    impl RewindHook for RecordingHook {
+        fn table_name(&self) -> &str {
+            &self.table_name
+        }
+
+        async fn rewind(&self, conn: &mut MyDbConnection, _target_version: u64) -> Result<usize> {
+            let status = processor_status::table
+                .filter(processor_status::processor.eq(PROCESSOR_NAME))
+                .first::<ProcessorStatusQuery>(conn)
+                .await?;
+            self.log
+                .lock()
+                .unwrap()
+                .push((self.table_name.clone(), status.last_success_version));
+            Ok(0)
+        }
+    }
+
+    struct FailingHook;
+
+    #[async_trait]
+    impl RewindHook for FailingHook {
+        fn table_name(&self) -> &str {
+            "failing"
+        }
+
+        async fn rewind(&self, _conn: &mut MyDbConnection, _target_version: u64) -> Result<usize> {
+            bail!("Cleanup failed")
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_rewind_processor() {
+        let (_db, db_pool) = setup_test_db().await;
+        setup_processor(&db_pool, 3600).await;
+
+        let summary = rewind_processor(
+            PROCESSOR_NAME,
+            options(5, false),
+            &[delete_events()],
+            db_pool.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(summary.previous_version, 9);
+        assert_eq!(summary.target_version, 5);
+        assert_eq!(summary.rows_affected, vec![("events".to_string(), 4)]);
+        // Rows at the target version are kept, since it has been processed.
+        assert_eq!(
+            event_versions(&db_pool).await,
+            (0..=5).collect::<Vec<i64>>()
+        );
+        assert_eq!(last_success_version(&db_pool).await, 5);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_rewind_processor_rejections() {
+        let (_db, db_pool) = setup_test_db().await;
+        // Updated just now, so the processor looks like it's still running.
+        setup_processor(&db_pool, 0).await;
+        let hooks = [delete_events()];
+
+        let error = rewind_processor("unknown", options(5, true), &hooks, db_pool.clone())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("No processor status found"));
+
+        // Even a forced rewind can't go forward.
+        let error = rewind_processor(PROCESSOR_NAME, options(10, true), &hooks, db_pool.clone())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Cannot rewind"));
+
+        let error = rewind_processor(PROCESSOR_NAME, options(5, false), &hooks, db_pool.clone())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("may still be running"));
+        assert_eq!(
+            event_versions(&db_pool).await,
+            (0..=9).collect::<Vec<i64>>()
+        );
+        assert_eq!(last_success_version(&db_pool).await, 9);
+
+        // Forcing skips the idle check.
+        rewind_processor(PROCESSOR_NAME, options(5, true), &hooks, db_pool.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            event_versions(&db_pool).await,
+            (0..=5).collect::<Vec<i64>>()
+        );
+        assert_eq!(last_success_version(&db_pool).await, 5);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_rewind_hook_ordering() {
+        let (_db, db_pool) = setup_test_db().await;
+        setup_processor(&db_pool, 3600).await;
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let recording_hook = |table_name: &str| -> Box<dyn RewindHook> {
+            Box::new(RecordingHook {
+                table_name: table_name.to_string(),
+                log: log.clone(),
+            })
+        };
+
+        // Hooks run in order, before the status is rewound.
+        let hooks = [
+            recording_hook("first"),
+            delete_events(),
+            recording_hook("second"),
+        ];
+        let summary = rewind_processor(PROCESSOR_NAME, options(5, false), &hooks, db_pool.clone())
+            .await
+            .unwrap();
+        assert_eq!(summary.rows_affected, vec![
+            ("first".to_string(), 0),
+            ("events".to_string(), 4),
+            ("second".to_string(), 0),
+        ]);
+        assert_eq!(*log.lock().unwrap(), vec![
+            ("first".to_string(), 9),
+            ("second".to_string(), 9),
+        ]);
+
+        // A failing hook rolls back the hooks before it and stops the ones after it.
+        log.lock().unwrap().clear();
+        let hooks: [Box<dyn RewindHook>; 3] = [
+            delete_events(),
+            Box::new(FailingHook),
+            recording_hook("after"),
+        ];
+        let error = rewind_processor(PROCESSOR_NAME, options(2, false), &hooks, db_pool.clone())
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("Cleanup failed"));
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(
+            event_versions(&db_pool).await,
+            (0..=5).collect::<Vec<i64>>()
+        );
+        assert_eq!(last_success_version(&db_pool).await, 5);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_delete_after_version() {
+        let (_db, db_pool) = setup_test_db().await;
+        setup_processor(&db_pool, 3600).await;
+        let mut conn = db_pool.get().await.unwrap();
+
+        let rows = delete_events().rewind(&mut conn, 7).await.unwrap();
+        assert_eq!(rows, 2);
+        // Nothing is after the target anymore.
+        let rows = delete_events().rewind(&mut conn, 7).await.unwrap();
+        assert_eq!(rows, 0);
+        drop(conn);
+        assert_eq!(
+            event_versions(&db_pool).await,
+            (0..=7).collect::<Vec<i64>>()
+        );
+
+        let mut conn = db_pool.get().await.unwrap();
+        let error = DeleteAfterVersion::new("missing", "transaction_version")
+            .rewind(&mut conn, 7)
+            .await
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Error deleting rows from missing"));
+    }
+}
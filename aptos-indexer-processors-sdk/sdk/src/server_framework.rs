@@ -1,30 +1,50 @@
 // Copyright © Aptos Foundation
 
-// Re-export health types for convenience.
-pub use crate::health::{
-    default_no_progress_threshold_secs, HealthCheck, ProgressHealthChecker, ProgressHealthConfig,
-    ProgressStatusProvider,
-};
 use crate::{
+    admin::admin_router,
+    config_reload::{ConfigReloadConfig, ConfigReloader},
+    health::run_health_checks,
     instrumented_channel::channel_metrics::init_channel_metrics_registry,
+    logging::LogSampleRate,
+    otel::{self, OtlpConfig},
     utils::step_metrics::init_step_metrics_registry,
 };
-use anyhow::{Context, Result};
+// Re-export health types for convenience.
+pub use crate::{
+    admin::AdminConfig,
+    health::{
+        default_no_progress_threshold_secs, ChainLagHealthChecker, ChainLagHealthConfig,
+        HealthCheck, HealthChecks, ProgressHealthChecker, ProgressHealthConfig,
+        ProgressStatusProvider, ReadinessGate, StreamHealthChecker, StreamHealthConfig,
+        TransactionTimestampProvider,
+    },
+    logging::{LogFormat, LoggingConfig},
+};
+use anyhow::{anyhow, bail, Context, Result};
 #[cfg(target_os = "linux")]
 use aptos_system_utils::profiling::start_cpu_profiling;
 use autometrics::settings::AutometricsSettings;
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{http::StatusCode, response::IntoResponse, routing::get, Json, Router};
 use backtrace::Backtrace;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use once_cell::sync::OnceCell;
 use prometheus_client::registry::Registry;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::sync::Arc;
+use serde_path_to_error::Segment;
+use serde_yaml::{Mapping, Value};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
 // TODO: remove deprecated lint when new clippy nightly is released.
 #[allow(deprecated)]
 use std::{fs::File, io::Read, panic::PanicInfo, path::PathBuf, process};
 use tokio::runtime::Handle;
-use tracing::error;
-use tracing_subscriber::EnvFilter;
+use tracing::{error, warn};
+use tracing_subscriber::{
+    fmt,
+    layer::{Layered, SubscriberExt},
+    reload,
+    util::SubscriberInitExt,
+    EnvFilter, Registry as TracingRegistry,
+};
 
 /// ServerArgs bootstraps a server with all common pieces. And then triggers the run method for
 /// the specific service.
@@ -32,6 +52,33 @@ use tracing_subscriber::EnvFilter;
 pub struct ServerArgs {
     #[clap(short, long, value_parser)]
     pub config_path: PathBuf,
+    /// Overrides a config value, e.g. `--set server_config.postgres_config.db_pool_size=50`.
+    /// Applied after the config file and `CONFIG_ENV_PREFIX` environment variables. Can be
+    /// repeated.
+    #[clap(long = "set", value_name = "KEY.PATH=VALUE")]
+    pub overrides: Vec<String>,
+    /// Runs a maintenance command instead of the server.
+    #[clap(subcommand)]
+    pub command: Option<ServerCommand>,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ServerCommand {
+    /// Rewinds the processor to an earlier version and exits. The processor must be stopped.
+    Rewind(RewindArgs),
+}
+
+#[derive(Args, Clone, Debug)]
+pub struct RewindArgs {
+    /// Version to rewind to. The processor resumes from this version on its next start.
+    #[clap(long)]
+    pub to_version: u64,
+    /// Refuse to rewind if the processor updated its status less than this many seconds ago.
+    #[clap(long, default_value_t = 300)]
+    pub min_idle_secs: u64,
+    /// Rewind even if the processor updated its status recently.
+    #[clap(long)]
+    pub force: bool,
 }
 
 impl ServerArgs {
@@ -39,21 +86,103 @@ impl ServerArgs {
     where
         C: RunnableConfig,
     {
-        self.run_with_health_checks::<C>(handle, vec![]).await
+        self.run_with_health_checks::<C>(handle, HealthChecks::default())
+            .await
     }
 
     pub async fn run_with_health_checks<C>(
         &self,
         handle: Handle,
-        health_checks: Vec<Arc<dyn HealthCheck>>,
+        health_checks: impl Into<HealthChecks>,
     ) -> Result<()>
     where
         C: RunnableConfig,
     {
-        setup_logging();
+        let config_value = self.load_config_value::<GenericConfig<C>>()?;
+        let config = deserialize_config::<GenericConfig<C>>(config_value.clone())?;
+        setup_logging_with_config(&config.logging_config, config.otlp_config.as_ref())?;
         setup_panic_handler();
-        let config = load::<GenericConfig<C>>(&self.config_path)?;
-        run_server_with_config(config, handle, health_checks).await
+        match &self.command {
+            Some(ServerCommand::Rewind(rewind_args)) => config.rewind(rewind_args).await,
+            None => {
+                self.start_config_reload(&config)?;
+                // Served by the admin `/config` endpoint.
+                let effective_config = serde_json::to_value(&config_value)?;
+                run_server(config, handle, health_checks.into(), Some(effective_config)).await
+            },
+        }
+    }
+
+    /// Loads the config file at `config_path`, with the overrides from `CONFIG_ENV_PREFIX`
+    /// environment variables and `--set` applied, see `load_layered`.
+    pub fn load_config<T: DeserializeOwned>(&self) -> Result<T> {
+        deserialize_config(self.load_config_value::<T>()?)
+    }
+
+    fn load_config_value<T: DeserializeOwned>(&self) -> Result<Value> {
+        load_layered::<T>(&self.config_path, std::env::vars(), &self.overrides)
+    }
+
+    /// Creates a `ConfigReloader` that loads the config the same way as `load_config`, and
+    /// rejects configs that don't deserialize into `T`.
+    pub fn config_reloader<T: DeserializeOwned + 'static>(&self) -> Result<ConfigReloader> {
+        let config_path = self.config_path.clone();
+        let overrides = self.overrides.clone();
+        ConfigReloader::new(self.config_path.clone(), move || {
+            let config = load_layered::<T>(&config_path, std::env::vars(), &overrides)?;
+            deserialize_config::<T>(config.clone())?;
+            Ok(config)
+        })
+    }
+
+    /// If `config_reload_config` is set, installs a `ConfigReloader`, see
+    /// `config_reload::config_reloader`, that reloads the config in the background and keeps
+    /// the log filter, levels and sampling up to date. Must be called from within the tokio
+    /// runtime.
+    pub fn start_config_reload<C: DeserializeOwned + 'static>(
+        &self,
+        config: &GenericConfig<C>,
+    ) -> Result<()> {
+        let Some(config_reload_config) = &config.config_reload_config else {
+            return Ok(());
+        };
+        let config_reloader = self.config_reloader::<GenericConfig<C>>()?.install()?;
+        // The format can't be changed once logging is set up, so it isn't subscribed to.
+        let mut filter = config_reloader.subscribe::<Option<String>>("logging_config.filter")?;
+        let mut levels = config_reloader
+            .subscribe::<Option<BTreeMap<String, String>>>("logging_config.levels")?;
+        let mut sampling = config_reloader
+            .subscribe::<Option<BTreeMap<String, LogSampleRate>>>("logging_config.sampling")?;
+        let format = config.logging_config.format;
+        tokio::spawn(async move {
+            loop {
+                let changed = tokio::select! {
+                    changed = filter.changed() => changed,
+                    changed = levels.changed() => changed,
+                    changed = sampling.changed() => changed,
+                };
+                if changed.is_err() {
+                    break;
+                }
+                let logging_config = LoggingConfig {
+                    filter: filter.borrow_and_update().clone(),
+                    format,
+                    levels: levels.borrow_and_update().clone().unwrap_or_default(),
+                    sampling: sampling.borrow_and_update().clone().unwrap_or_default(),
+                };
+                if let Err(e) = apply_logging_config(&logging_config) {
+                    warn!(
+                        error = format!("{e:#}"),
+                        "Failed to update the logging config"
+                    );
+                }
+            }
+        });
+        let watch_interval = config_reload_config
+            .watch_interval_secs
+            .map(Duration::from_secs);
+        tokio::spawn(config_reloader.watch(watch_interval));
+        Ok(())
     }
 }
 
@@ -62,27 +191,51 @@ impl ServerArgs {
 pub async fn run_server_with_config<C>(
     config: GenericConfig<C>,
     handle: Handle,
-    health_checks: Vec<Arc<dyn HealthCheck>>,
+    health_checks: impl Into<HealthChecks>,
+) -> Result<()>
+where
+    C: RunnableConfig,
+{
+    run_server(config, handle, health_checks.into(), None).await
+}
+
+async fn run_server<C>(
+    config: GenericConfig<C>,
+    handle: Handle,
+    health_checks: HealthChecks,
+    effective_config: Option<serde_json::Value>,
 ) -> Result<()>
 where
     C: RunnableConfig,
 {
     let health_port = config.health_check_port;
     let additional_labels = config.metrics_config.additional_labels.clone();
+    let admin_router = config
+        .admin_config
+        .as_ref()
+        .map(|admin_config| admin_router(admin_config, effective_config));
     // Start health and metrics probes.
     let task_handler = handle.spawn(async move {
-        register_probes_and_metrics_handler(health_port, additional_labels, health_checks).await;
+        register_probes_metrics_and_admin_handler(
+            health_port,
+            additional_labels,
+            health_checks,
+            admin_router,
+        )
+        .await;
         anyhow::Ok(())
     });
     let main_task_handler = handle.spawn(async move { config.run().await });
-    tokio::select! {
+    let res = tokio::select! {
         res = task_handler => {
             res.expect("Probes and metrics handler unexpectedly exited")
         },
         res = main_task_handler => {
             res.expect("Main task handler unexpectedly exited")
         },
-    }
+    };
+    otel::shutdown().await;
+    res
 }
 
 #[derive(Deserialize, Debug, Serialize)]
@@ -93,6 +246,23 @@ pub struct GenericConfig<T> {
     #[serde(default)]
     pub metrics_config: MetricsConfig,
 
+    /// Optional admin endpoints for pausing, resuming and inspecting the processor, see
+    /// `admin::admin_router`.
+    #[serde(default)]
+    pub admin_config: Option<AdminConfig>,
+
+    /// Log format, levels and sampling, see `logging`.
+    #[serde(default)]
+    pub logging_config: LoggingConfig,
+
+    /// Optional export of the per-batch tracing spans, see `otel`.
+    #[serde(default)]
+    pub otlp_config: Option<OtlpConfig>,
+
+    /// Optional hot reloading of the settings that support it, see `config_reload`.
+    #[serde(default)]
+    pub config_reload_config: Option<ConfigReloadConfig>,
+
     // Specific configuration for each service.
     pub server_config: T,
 }
@@ -112,6 +282,10 @@ where
         self.server_config.run().await
     }
 
+    async fn rewind(&self, rewind_args: &RewindArgs) -> Result<()> {
+        self.server_config.rewind(rewind_args).await
+    }
+
     fn get_server_name(&self) -> String {
         self.server_config.get_server_name()
     }
@@ -121,17 +295,183 @@ where
 #[async_trait::async_trait]
 pub trait RunnableConfig: DeserializeOwned + Send + Sync + 'static {
     async fn run(&self) -> Result<()>;
+
+    /// Handles the `rewind` subcommand. Services that support rewinding typically call
+    /// `postgres::utils::rewind::rewind_processor` with their cleanup hooks.
+    async fn rewind(&self, _rewind_args: &RewindArgs) -> Result<()> {
+        anyhow::bail!("{} does not support rewinding", self.get_server_name())
+    }
+
     fn get_server_name(&self) -> String;
 }
 
+/// Prefix of the environment variables that override config values. The rest of the name is
+/// the key path in uppercase, with `__` between keys. For example,
+/// `APTOS_INDEXER__SERVER_CONFIG__POSTGRES_CONFIG__CONNECTION_STRING` overrides
+/// `server_config.postgres_config.connection_string`.
+pub const CONFIG_ENV_PREFIX: &str = "APTOS_INDEXER__";
+const CONFIG_ENV_KEY_SEPARATOR: &str = "__";
+
 /// Parse a yaml file into a struct.
 pub fn load<T: for<'de> Deserialize<'de>>(path: &PathBuf) -> Result<T> {
+    deserialize_config(read_yaml(path)?)
+}
+
+/// Reads the yaml file at `path`, then applies the overrides from the `CONFIG_ENV_PREFIX`
+/// variables in `env_vars`, then the `key.path=value` `overrides`. Later layers win.
+///
+/// Values are parsed as yaml, so numbers, booleans, lists (`[1, 2]`) and maps (`{a: 1}`)
+/// can be set. A value replacing a string in the file is always kept as a string, so tokens
+/// that look like numbers aren't mangled. So is a value of a key that is absent or null in
+/// the file, unless `T` doesn't take a string there. Elements of lists are addressed by
+/// index, e.g. `server_config.sinks.0.url`.
+pub fn load_layered<T: DeserializeOwned>(
+    path: &PathBuf,
+    env_vars: impl IntoIterator<Item = (String, String)>,
+    overrides: &[String],
+) -> Result<Value> {
+    let mut config = read_yaml(path)?;
+    // Key paths whose values are strings only because the file doesn't say otherwise.
+    let mut untyped_overrides = vec![];
+
+    let mut env_overrides: Vec<(String, String)> = env_vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(CONFIG_ENV_PREFIX))
+        .collect();
+    // Apply in a deterministic order.
+    env_overrides.sort();
+    for (name, value) in env_overrides {
+        let key_path: Vec<String> = name[CONFIG_ENV_PREFIX.len()..]
+            .split(CONFIG_ENV_KEY_SEPARATOR)
+            .map(|key| key.to_lowercase())
+            .collect();
+        set_config_value(&mut config, &key_path, &value, &mut untyped_overrides)
+            .with_context(|| format!("Invalid config override from environment variable {name}"))?;
+    }
+
+    for config_override in overrides {
+        let (key_path, value) = config_override.split_once('=').ok_or_else(|| {
+            anyhow!("Invalid config override `--set {config_override}`, expected KEY.PATH=VALUE")
+        })?;
+        let key_path: Vec<String> = key_path.split('.').map(str::to_string).collect();
+        set_config_value(&mut config, &key_path, value, &mut untyped_overrides)
+            .with_context(|| format!("Invalid config override `--set {config_override}`"))?;
+    }
+    parse_untyped_overrides::<T>(&mut config, untyped_overrides);
+    Ok(config)
+}
+
+fn read_yaml(path: &PathBuf) -> Result<Value> {
     let mut file =
         File::open(path).with_context(|| format!("failed to open the file at path: {path:?}",))?;
     let mut contents = String::new();
     file.read_to_string(&mut contents)
         .with_context(|| format!("failed to read the file at path: {path:?}",))?;
-    serde_yaml::from_str::<T>(&contents).context("Unable to parse yaml file")
+    serde_yaml::from_str::<Value>(&contents).context("Unable to parse yaml file")
+}
+
+/// Deserializes `config`, naming the key path of the offending value on error.
+pub fn deserialize_config<T: DeserializeOwned>(config: Value) -> Result<T> {
+    serde_path_to_error::deserialize(config)
+        .map_err(|e| anyhow!("Invalid config at `{}`: {}", e.path(), e.inner()))
+}
+
+fn set_config_value(
+    config: &mut Value,
+    key_path: &[String],
+    raw_value: &str,
+    untyped_overrides: &mut Vec<Vec<String>>,
+) -> Result<()> {
+    if key_path.iter().any(|key| key.is_empty()) {
+        bail!("Empty key in `{}`", key_path.join("."));
+    }
+    let mut current = config;
+    for (i, key) in key_path.iter().enumerate() {
+        if current.is_null() {
+            *current = Value::Mapping(Mapping::new());
+        }
+        current = match current {
+            Value::Mapping(mapping) => {
+                let key = Value::String(key.clone());
+                if !mapping.contains_key(&key) {
+                    mapping.insert(key.clone(), Value::Null);
+                }
+                mapping.get_mut(&key).unwrap()
+            },
+            Value::Sequence(sequence) => {
+                let len = sequence.len();
+                let index = key
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| *index < len)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "`{}` is a list of {} elements, `{}` is not an index into it",
+                            key_path[..i].join("."),
+                            len,
+                            key
+                        )
+                    })?;
+                &mut sequence[index]
+            },
+            _ => bail!("`{}` is not a map or a list", key_path[..i].join(".")),
+        };
+    }
+    if current.is_null() && !untyped_overrides.iter().any(|path| path == key_path) {
+        untyped_overrides.push(key_path.to_vec());
+    }
+    *current = match current {
+        Value::String(_) | Value::Null => Value::String(raw_value.to_string()),
+        _ => {
+            serde_yaml::from_str(raw_value).unwrap_or_else(|_| Value::String(raw_value.to_string()))
+        },
+    };
+    Ok(())
+}
+
+/// Parses the values at `untyped_overrides` as yaml where `T` fails to deserialize them as
+/// strings, one at a time, until `T` deserializes or fails elsewhere.
+fn parse_untyped_overrides<T: DeserializeOwned>(
+    config: &mut Value,
+    mut untyped_overrides: Vec<Vec<String>>,
+) {
+    while !untyped_overrides.is_empty() {
+        let Err(e) = serde_path_to_error::deserialize::<_, T>(config.clone()) else {
+            return;
+        };
+        let error_path: Vec<String> = e
+            .path()
+            .iter()
+            .map(|segment| match segment {
+                Segment::Seq { index } => index.to_string(),
+                Segment::Map { key } => key.clone(),
+                Segment::Enum { variant } => variant.clone(),
+                Segment::Unknown => "?".to_string(),
+            })
+            .collect();
+        let Some(position) = untyped_overrides
+            .iter()
+            .position(|key_path| *key_path == error_path)
+        else {
+            return;
+        };
+        let key_path = untyped_overrides.swap_remove(position);
+        let value = key_path.iter().try_fold(&mut *config, |current, key| {
+            if current.is_sequence() {
+                current.get_mut(key.parse::<usize>().ok()?)
+            } else {
+                current.get_mut(key.as_str())
+            }
+        });
+        if let Some(value) = value {
+            if let Some(parsed) = value
+                .as_str()
+                .and_then(|raw_value| serde_yaml::from_str::<Value>(raw_value).ok())
+            {
+                *value = parsed;
+            }
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -171,32 +511,134 @@ fn handle_panic(panic_info: &PanicInfo<'_>) {
     process::exit(12);
 }
 
+static LOG_FILTER_HANDLE: OnceCell<reload::Handle<EnvFilter, TracingRegistry>> = OnceCell::new();
+
+fn default_env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new("info"))
+        .unwrap()
+}
+
 /// Set up logging for the server.
 pub fn setup_logging() {
-    let env_filter = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new("info"))
-        .unwrap();
-    tracing_subscriber::fmt()
-        .json()
-        .with_file(true)
-        .with_line_number(true)
-        .with_thread_ids(true)
-        .with_target(false)
-        .with_thread_names(true)
-        .with_env_filter(env_filter)
-        .flatten_event(true)
+    setup_logging_with_config(&LoggingConfig::default(), None)
+        .expect("Setting up logging with the default config can't fail");
+}
+
+/// Same as `setup_logging`, but logs as configured by `logging_config`, and also exports
+/// the tracing spans as configured by `otlp_config`, if set. Must be called from within the
+/// tokio runtime.
+pub fn setup_logging_with_config(
+    logging_config: &LoggingConfig,
+    otlp_config: Option<&OtlpConfig>,
+) -> Result<()> {
+    let (env_filter, handle) = reload::Layer::new(logging_env_filter(logging_config)?);
+    let registry = tracing_subscriber::registry().with(env_filter);
+    let otlp_layer = otel::otlp_layer::<
+        Layered<reload::Layer<EnvFilter, TracingRegistry>, TracingRegistry>,
+    >(otlp_config)?;
+    let format = logging_config.format;
+    registry
+        .with(otlp_layer)
+        .with((format == LogFormat::Json).then(|| {
+            fmt::layer()
+                .json()
+                .with_file(true)
+                .with_line_number(true)
+                .with_thread_ids(true)
+                .with_target(false)
+                .with_thread_names(true)
+                .flatten_event(true)
+                // The per-batch spans are for tracing, keep them out of the logs.
+                .with_current_span(false)
+                .with_span_list(false)
+        }))
+        .with((format == LogFormat::Pretty).then(|| {
+            fmt::layer()
+                .pretty()
+                .with_file(true)
+                .with_line_number(true)
+                .with_thread_names(true)
+        }))
+        .with((format == LogFormat::Compact).then(|| fmt::layer().compact()))
         .init();
+    let _ = LOG_FILTER_HANDLE.set(handle);
+    sample::set_sample_rates(logging_config.sample_rates());
+    Ok(())
+}
+
+/// Applies the filter, levels and sampling of `logging_config` to the logging set up by
+/// `setup_logging`. The format can't be changed.
+pub fn apply_logging_config(logging_config: &LoggingConfig) -> Result<()> {
+    reload_log_filter(logging_env_filter(logging_config)?)?;
+    sample::set_sample_rates(logging_config.sample_rates());
+    Ok(())
+}
+
+// `filter`, or `RUST_LOG` if it isn't set, with the `levels` on top.
+fn logging_env_filter(logging_config: &LoggingConfig) -> Result<EnvFilter> {
+    let env_filter = match &logging_config.filter {
+        Some(filter) => {
+            EnvFilter::try_new(filter).with_context(|| format!("Invalid log filter `{filter}`"))?
+        },
+        None => default_env_filter(),
+    };
+    logging_config
+        .levels
+        .iter()
+        .try_fold(env_filter, |env_filter, (target, level)| {
+            let directive = format!("{target}={level}")
+                .parse()
+                .with_context(|| format!("Invalid log level `{level}` for `{target}`"))?;
+            Ok(env_filter.add_directive(directive))
+        })
+}
+
+/// Replaces the log filter set up by `setup_logging` with `filter`, in `EnvFilter` syntax.
+pub fn set_log_filter(filter: &str) -> Result<()> {
+    let env_filter =
+        EnvFilter::try_new(filter).with_context(|| format!("Invalid log filter `{filter}`"))?;
+    reload_log_filter(env_filter)
+}
+
+/// Restores the log filter from `RUST_LOG`, or `info` if it isn't set.
+pub fn set_log_filter_to_default() -> Result<()> {
+    reload_log_filter(default_env_filter())
+}
+
+fn reload_log_filter(env_filter: EnvFilter) -> Result<()> {
+    LOG_FILTER_HANDLE
+        .get()
+        .context("Logging was not set up with setup_logging")?
+        .reload(env_filter)
+        .context("Failed to reload the log filter")
 }
 
 /// Register health and metrics probes and set up metrics endpoint.
 ///
-/// If `health_checks` is provided, the `/healthz` endpoint will call each check
-/// and return 503 if any of them fail, with the failure reasons in the response body.
+/// `/livez` and `/readyz` run the liveness and readiness checks respectively, concurrently
+/// and with a timeout per check. They return 503 if any check fails, and a JSON
+/// `HealthReport` with every check's result in the body.
+///
+/// `/healthz` runs the liveness checks too, but responds in plain text: the failure reasons
+/// if any check fails, otherwise the status of every check that reports one.
 pub async fn register_probes_and_metrics_handler(
     port: u16,
     additional_labels: Vec<(String, String)>,
-    health_checks: Vec<Arc<dyn HealthCheck>>,
+    health_checks: impl Into<HealthChecks>,
 ) {
+    register_probes_metrics_and_admin_handler(port, additional_labels, health_checks, None).await
+}
+
+/// Same as `register_probes_and_metrics_handler`, but also serves `admin_router` if provided,
+/// see `admin::admin_router`.
+pub async fn register_probes_metrics_and_admin_handler(
+    port: u16,
+    additional_labels: Vec<(String, String)>,
+    health_checks: impl Into<HealthChecks>,
+    admin_router: Option<Router>,
+) {
+    let health_checks = Arc::new(health_checks.into());
     let mut registry = Registry::with_labels(
         additional_labels
             .into_iter()
@@ -208,43 +650,72 @@ pub async fn register_probes_and_metrics_handler(
         .prometheus_client_registry(registry)
         .init();
 
-    let router = if health_checks.is_empty() {
-        Router::new()
-            .route("/healthz", get(StatusCode::OK))
-            .route("/metrics", get(metrics_handler))
-    } else {
+    let router =
         Router::new()
             .route(
                 "/healthz",
                 get({
-                    let checks = health_checks.clone();
-                    move || health_handler(checks.clone())
+                    let health_checks = health_checks.clone();
+                    move || health_handler(health_checks.clone())
                 }),
             )
-            .route("/metrics", get(metrics_handler))
-    };
+            .route(
+                "/livez",
+                get({
+                    let health_checks = health_checks.clone();
+                    move || {
+                        let health_checks = health_checks.clone();
+                        async move {
+                            health_report_handler(&health_checks.liveness, &health_checks).await
+                        }
+                    }
+                }),
+            )
+            .route(
+                "/readyz",
+                get({
+                    let health_checks = health_checks.clone();
+                    move || {
+                        let health_checks = health_checks.clone();
+                        async move {
+                            health_report_handler(&health_checks.readiness, &health_checks).await
+                        }
+                    }
+                }),
+            )
+            .route("/metrics", get(metrics_handler));
 
     #[cfg(target_os = "linux")]
     let router = router.merge(Router::new().route("/profilez", get(profilez_handler)));
 
+    let router = match admin_router {
+        Some(admin_router) => router.merge(admin_router),
+        None => router,
+    };
+
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}",))
         .await
         .expect("Failed to bind TCP listener");
     axum::serve(listener, router).await.unwrap();
 }
 
-/// Health handler that runs all registered checks.
-async fn health_handler(checks: Vec<Arc<dyn HealthCheck>>) -> impl IntoResponse {
+/// Health handler that runs all liveness checks.
+async fn health_handler(health_checks: Arc<HealthChecks>) -> impl IntoResponse {
+    let report = run_health_checks(&health_checks.liveness, health_checks.check_timeout).await;
     let mut failures = Vec::new();
+    let mut statuses = Vec::new();
 
-    for check in &checks {
-        if let Err(reason) = check.is_healthy().await {
-            failures.push(format!("{}: {}", check.name(), reason));
+    for check in report.checks {
+        if let Some(reason) = check.reason {
+            failures.push(format!("{}: {}", check.name, reason));
+        }
+        if let Some(status) = check.details {
+            statuses.push(format!("{}: {}", check.name, status));
         }
     }
 
     if failures.is_empty() {
-        StatusCode::OK.into_response()
+        (StatusCode::OK, statuses.join("\n")).into_response()
     } else {
         (
             StatusCode::SERVICE_UNAVAILABLE,
@@ -254,6 +725,20 @@ async fn health_handler(checks: Vec<Arc<dyn HealthCheck>>) -> impl IntoResponse
     }
 }
 
+/// Runs `checks` and returns the report as JSON.
+async fn health_report_handler(
+    checks: &[Arc<dyn HealthCheck>],
+    health_checks: &HealthChecks,
+) -> impl IntoResponse {
+    let report = run_health_checks(checks, health_checks.check_timeout).await;
+    let status = if report.is_healthy() {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
 async fn metrics_handler() -> impl IntoResponse {
     match autometrics::prometheus_exporter::encode_to_string() {
         Ok(prometheus_client_rust_metrics) => (
@@ -333,6 +818,133 @@ mod tests {
         assert_eq!(config.server_config.test_name, "test");
     }
 
+    #[test]
+    fn test_load_layered_overrides() {
+        let dir = tempdir().expect("tempdir failure");
+        let file_path = dir.path().join("testing_yaml.yaml");
+        let mut file = File::create(&file_path).expect("create failure");
+        let raw_yaml_content = r#"
+            health_check_port: 12345
+            server_config:
+                test: 1
+                test_name: "from_file"
+        "#;
+        writeln!(file, "{raw_yaml_content}").expect("write_all failure");
+
+        let env_vars = [
+            (
+                "APTOS_INDEXER__SERVER_CONFIG__TEST".to_string(),
+                "2".to_string(),
+            ),
+            // Stays a string even though it parses as a number.
+            (
+                "APTOS_INDEXER__SERVER_CONFIG__TEST_NAME".to_string(),
+                "456".to_string(),
+            ),
+            ("HEALTH_CHECK_PORT".to_string(), "1".to_string()),
+        ];
+        let overrides = ["server_config.test=123".to_string()];
+        let config = deserialize_config::<GenericConfig<TestConfig>>(
+            load_layered::<GenericConfig<TestConfig>>(&file_path, env_vars, &overrides).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.health_check_port, 12345);
+        assert_eq!(config.server_config.test, 123);
+        assert_eq!(config.server_config.test_name, "456");
+
+        let error = load_layered::<GenericConfig<TestConfig>>(
+            &file_path,
+            [],
+            &["health_check_port.test=1".to_string()],
+        )
+        .unwrap_err();
+        assert!(format!("{error:#}").contains("`health_check_port` is not a map or a list"));
+
+        let error = deserialize_config::<GenericConfig<TestConfig>>(
+            load_layered::<GenericConfig<TestConfig>>(
+                &file_path,
+                [],
+                &["server_config.test=abc".to_string()],
+            )
+            .unwrap(),
+        )
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid config at `server_config.test`"));
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct SecretsConfig {
+        auth_token: String,
+        connection_string: String,
+        #[serde(default)]
+        db_pool_size: Option<u32>,
+        #[serde(default)]
+        labels: Vec<String>,
+    }
+
+    #[test]
+    fn test_load_layered_overrides_of_absent_keys() {
+        let dir = tempdir().expect("tempdir failure");
+        let file_path = dir.path().join("testing_yaml.yaml");
+        let mut file = File::create(&file_path).expect("create failure");
+        let raw_yaml_content = r#"
+            health_check_port: 12345
+            server_config:
+                labels: null
+        "#;
+        writeln!(file, "{raw_yaml_content}").expect("write_all failure");
+
+        let env_vars = [
+            // Would be parsed as a float.
+            (
+                "APTOS_INDEXER__SERVER_CONFIG__AUTH_TOKEN".to_string(),
+                "1e10".to_string(),
+            ),
+            // Would be parsed as a map.
+            (
+                "APTOS_INDEXER__SERVER_CONFIG__CONNECTION_STRING".to_string(),
+                "host: localhost".to_string(),
+            ),
+            (
+                "APTOS_INDEXER__SERVER_CONFIG__DB_POOL_SIZE".to_string(),
+                "20".to_string(),
+            ),
+        ];
+        let overrides = ["server_config.labels=[a, b]".to_string()];
+        let config = deserialize_config::<GenericConfig<SecretsConfig>>(
+            load_layered::<GenericConfig<SecretsConfig>>(&file_path, env_vars, &overrides).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(config.server_config.auth_token, "1e10");
+        assert_eq!(config.server_config.connection_string, "host: localhost");
+        assert_eq!(config.server_config.db_pool_size, Some(20));
+        assert_eq!(config.server_config.labels, ["a", "b"]);
+    }
+
+    #[test]
+    fn test_parse_rewind_command() {
+        let args = ServerArgs::try_parse_from([
+            "processor",
+            "-c",
+            "config.yaml",
+            "rewind",
+            "--to-version",
+            "100",
+        ])
+        .unwrap();
+        match args.command {
+            Some(ServerCommand::Rewind(rewind_args)) => {
+                assert_eq!(rewind_args.to_version, 100);
+                assert_eq!(rewind_args.min_idle_secs, 300);
+                assert!(!rewind_args.force);
+            },
+            None => panic!("Expected the rewind command"),
+        }
+    }
+
     #[test]
     fn verify_tool() {
         use clap::CommandFactory;
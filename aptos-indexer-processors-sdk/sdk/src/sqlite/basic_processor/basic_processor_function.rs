@@ -0,0 +1,275 @@
+use super::basic_processor_step::BasicProcessorStep;
+use crate::{
+    admin::admin_router,
+    aptos_indexer_transaction_stream::{StreamConnectionStats, TransactionStreamConfig},
+    builder::ProcessorBuilder,
+    common_steps::{
+        TransactionStreamStep, VersionTrackerStep, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS,
+    },
+    config_reload::config_reloader,
+    otel,
+    server_framework::{
+        register_probes_metrics_and_admin_handler, setup_logging_with_config, setup_panic_handler,
+        ChainLagHealthChecker, ChainLagHealthConfig, GenericConfig, HealthCheck, HealthChecks,
+        ProgressHealthChecker, ProgressHealthConfig, ReadinessGate, ServerArgs,
+        StreamHealthChecker, StreamHealthConfig,
+    },
+    sqlite::{
+        progress::SqliteProgressStatusProvider,
+        subconfigs::sqlite_config::SqliteConfig,
+        utils::{
+            checkpoint::{get_starting_version, SqliteChainIdChecker, SqliteProcessorStatusSaver},
+            database::{new_db_pool, run_user_and_sdk_migrations, ArcDbPool, DbPoolConnection},
+        },
+    },
+    traits::IntoRunnableStep,
+    utils::{chain_id_check::check_or_update_chain_id, errors::ProcessorError},
+};
+use anyhow::Result;
+use aptos_protos::transaction::v1::Transaction;
+use clap::Parser;
+use diesel_migrations::EmbeddedMigrations;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProcessConfig {
+    pub transaction_stream_config: TransactionStreamConfig,
+    pub sqlite_config: SqliteConfig,
+    /// Optional configuration for progress health checking.
+    /// If provided, the `/livez` and `/healthz` endpoints will check if the processor is making
+    /// progress. A processor that stopped making progress is wedged, so this is a liveness
+    /// check and failing it restarts the pod.
+    #[serde(default)]
+    pub progress_health_config: Option<ProgressHealthConfig>,
+    /// Optional configuration for chain lag health checking.
+    /// If provided, the `/readyz` endpoint will check that the last processed transaction
+    /// isn't too far behind the current time.
+    #[serde(default)]
+    pub chain_lag_health_config: Option<ChainLagHealthConfig>,
+    /// Optional configuration for transaction stream health checking.
+    /// If provided, the `/readyz` endpoint will check that the stream keeps delivering
+    /// transactions and isn't reconnecting repeatedly.
+    #[serde(default)]
+    pub stream_health_config: Option<StreamHealthConfig>,
+}
+
+/// Processes transactions with a custom handler function, which is given a SQLite
+/// connection for each batch.
+pub async fn process<F, Fut>(
+    processor_name: String,
+    embedded_migrations: EmbeddedMigrations,
+    process_function: F,
+) -> Result<()>
+where
+    F: FnMut(Vec<Transaction>, DbPoolConnection<'static>) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
+{
+    let args = ServerArgs::parse();
+    let config = args.load_config::<GenericConfig<ProcessConfig>>()?;
+    setup_logging_with_config(&config.logging_config, config.otlp_config.as_ref())?;
+    setup_panic_handler();
+    let handle = tokio::runtime::Handle::current();
+    args.start_config_reload(&config)?;
+
+    let health_port = config.health_check_port;
+    let additional_labels = config.metrics_config.additional_labels.clone();
+    let progress_health_config = config.server_config.progress_health_config.clone();
+    let chain_lag_health_config = config.server_config.chain_lag_health_config.clone();
+    let stream_health_config = config.server_config.stream_health_config.clone();
+
+    let db_pool = new_db_pool(
+        &config.server_config.sqlite_config.database_path,
+        config.server_config.sqlite_config.db_pool_size,
+    )
+    .await
+    .expect("Failed to create connection pool");
+
+    // Build health checks. Only a wedged processor fails liveness and gets restarted. Failing
+    // dependencies like the DB or the stream take the pod out of rotation until they recover.
+    let mut liveness_checks: Vec<Arc<dyn HealthCheck>> = vec![];
+    let mut readiness_checks: Vec<Arc<dyn HealthCheck>> = vec![];
+    if let Some(progress_config) = progress_health_config {
+        let status_provider =
+            SqliteProgressStatusProvider::new(processor_name.clone(), db_pool.clone());
+        let mut progress_checker = ProgressHealthChecker::new(
+            processor_name.clone(),
+            Box::new(status_provider),
+            progress_config,
+        );
+        if let Some(config_reloader) = config_reloader() {
+            progress_checker = progress_checker.with_config_updates(
+                config_reloader.subscribe("server_config.progress_health_config")?,
+            );
+        }
+        liveness_checks.push(Arc::new(progress_checker));
+    }
+    if let Some(chain_lag_config) = chain_lag_health_config {
+        let timestamp_provider =
+            SqliteProgressStatusProvider::new(processor_name.clone(), db_pool.clone());
+        let mut chain_lag_checker = ChainLagHealthChecker::new(
+            processor_name.clone(),
+            Box::new(timestamp_provider),
+            chain_lag_config,
+        );
+        if let Some(config_reloader) = config_reloader() {
+            chain_lag_checker = chain_lag_checker.with_config_updates(
+                config_reloader.subscribe("server_config.chain_lag_health_config")?,
+            );
+        }
+        readiness_checks.push(Arc::new(chain_lag_checker));
+    }
+    let stream_connection_stats = match stream_health_config {
+        Some(stream_health_config) => {
+            let stream_connection_stats = StreamConnectionStats::new();
+            let mut stream_checker =
+                StreamHealthChecker::new(stream_connection_stats.clone(), stream_health_config);
+            if let Some(config_reloader) = config_reloader() {
+                stream_checker = stream_checker.with_config_updates(
+                    config_reloader.subscribe("server_config.stream_health_config")?,
+                );
+            }
+            readiness_checks.push(Arc::new(stream_checker));
+            Some(stream_connection_stats)
+        },
+        None => None,
+    };
+
+    // Not ready until migrations have run and the chain id is verified.
+    let readiness_gate = ReadinessGate::new("Startup");
+    readiness_checks.push(Arc::new(readiness_gate.clone()));
+    let health_checks = HealthChecks {
+        liveness: liveness_checks,
+        readiness: readiness_checks,
+        ..HealthChecks::default()
+    };
+
+    let admin_router = config
+        .admin_config
+        .as_ref()
+        .map(|admin_config| admin_router(admin_config, serde_json::to_value(&config).ok()));
+
+    // Start health, metrics and admin probes.
+    let task_handler = handle.spawn(async move {
+        register_probes_metrics_and_admin_handler(
+            health_port,
+            additional_labels,
+            health_checks,
+            admin_router,
+        )
+        .await;
+        anyhow::Ok(())
+    });
+    let main_task_handler = handle.spawn(async move {
+        run_processor(
+            processor_name,
+            config.server_config.transaction_stream_config,
+            config.server_config.sqlite_config,
+            embedded_migrations,
+            db_pool,
+            process_function,
+            RunProcessorOptions {
+                readiness_gate: Some(readiness_gate),
+                stream_connection_stats,
+            },
+        )
+        .await
+    });
+    let res = tokio::select! {
+        res = task_handler => {
+            res.expect("Probes and metrics handler unexpectedly exited")
+        },
+        res = main_task_handler => {
+            res.expect("Main task handler unexpectedly exited")
+        },
+    };
+    otel::shutdown().await;
+    res
+}
+
+/// Optional parts of the pipeline run by `run_processor`. Everything is off by default.
+#[derive(Default)]
+pub struct RunProcessorOptions {
+    /// Marked ready once the migrations have run and the chain id is verified.
+    pub readiness_gate: Option<ReadinessGate>,
+    /// Connection stats of the transaction stream, for `StreamHealthChecker`.
+    pub stream_connection_stats: Option<StreamConnectionStats>,
+}
+
+/// Runs the processor pipeline, with the optional parts set in `options`.
+pub async fn run_processor<F, Fut>(
+    processor_name: String,
+    transaction_stream_config: TransactionStreamConfig,
+    sqlite_config: SqliteConfig,
+    embedded_migrations: EmbeddedMigrations,
+    db_pool: ArcDbPool,
+    process_function: F,
+    options: RunProcessorOptions,
+) -> Result<()>
+where
+    F: FnMut(Vec<Transaction>, DbPoolConnection<'static>) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
+{
+    let RunProcessorOptions {
+        readiness_gate,
+        stream_connection_stats,
+    } = options;
+
+    // Run user migrations, then SDK migrations.
+    run_user_and_sdk_migrations(sqlite_config.database_path.clone(), embedded_migrations).await;
+
+    check_or_update_chain_id(
+        &transaction_stream_config,
+        &SqliteChainIdChecker::new(db_pool.clone()),
+    )
+    .await?;
+    if let Some(readiness_gate) = &readiness_gate {
+        readiness_gate.mark_ready();
+    }
+
+    // Merge the starting version from config and the latest processed version from the DB.
+    let starting_version = get_starting_version(
+        processor_name.as_str(),
+        transaction_stream_config.clone(),
+        db_pool.clone(),
+    )
+    .await?;
+
+    // Define processor steps.
+    let mut transaction_stream = TransactionStreamStep::new(TransactionStreamConfig {
+        starting_version: Some(starting_version),
+        ..transaction_stream_config
+    })
+    .await?;
+    if let Some(stream_connection_stats) = stream_connection_stats {
+        transaction_stream = transaction_stream.with_connection_stats(stream_connection_stats);
+    }
+    let basic_processor_step = BasicProcessorStep {
+        process_function,
+        conn_pool: db_pool.clone(),
+    };
+    let processor_status_saver =
+        SqliteProcessorStatusSaver::new(processor_name.as_str(), db_pool.clone());
+    let version_tracker =
+        VersionTrackerStep::new(processor_status_saver, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS);
+
+    // Connect processor steps together.
+    let (_, buffer_receiver) =
+        ProcessorBuilder::new_with_inputless_first_step(transaction_stream.into_runnable_step())
+            .connect_to(basic_processor_step.into_runnable_step(), 10)
+            .connect_to(version_tracker.into_runnable_step(), 10)
+            .end_and_return_output_receiver(10);
+
+    // (Optional) Parse the results.
+    loop {
+        match buffer_receiver.recv().await {
+            Ok(_) => {},
+            Err(_) => {
+                info!("Channel is closed");
+                return Ok(());
+            },
+        }
+    }
+}
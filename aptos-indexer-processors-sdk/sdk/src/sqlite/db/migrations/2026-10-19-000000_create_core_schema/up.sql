@@ -0,0 +1,12 @@
+-- SQLite has no schemas, so the SDK tables live next to the processor's own tables.
+
+-- Tracks latest processed version per processor
+CREATE TABLE IF NOT EXISTS processor_status (
+  processor VARCHAR(100) UNIQUE PRIMARY KEY NOT NULL,
+  last_success_version BIGINT NOT NULL,
+  last_updated TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_transaction_timestamp TIMESTAMP NULL
+);
+
+-- Tracks chain id
+CREATE TABLE IF NOT EXISTS ledger_infos (chain_id BIGINT UNIQUE PRIMARY KEY NOT NULL);
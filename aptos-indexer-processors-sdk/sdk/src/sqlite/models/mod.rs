@@ -0,0 +1,2 @@
+pub mod ledger_info;
+pub mod processor_status;
@@ -0,0 +1,63 @@
+//! SQLite-specific progress health checking.
+//!
+//! This module provides `SqliteProgressStatusProvider`, which implements the
+//! `ProgressStatusProvider` trait for SQLite-backed processors.
+
+use crate::{
+    health::{ProgressStatusProvider, TransactionTimestampProvider},
+    sqlite::{models::processor_status::ProcessorStatusQuery, utils::database::ArcDbPool},
+};
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+
+/// A SQLite-backed implementation of `ProgressStatusProvider`.
+///
+/// This queries the `processor_status` table to get the last updated timestamp, and the
+/// last transaction timestamp for `ChainLagHealthChecker`.
+pub struct SqliteProgressStatusProvider {
+    processor_name: String,
+    db_pool: ArcDbPool,
+}
+
+impl SqliteProgressStatusProvider {
+    pub fn new(processor_name: String, db_pool: ArcDbPool) -> Self {
+        Self {
+            processor_name,
+            db_pool,
+        }
+    }
+}
+
+#[async_trait]
+impl ProgressStatusProvider for SqliteProgressStatusProvider {
+    async fn get_last_updated(&self) -> Result<Option<NaiveDateTime>, String> {
+        let mut conn = self
+            .db_pool
+            .get()
+            .await
+            .map_err(|e| format!("Failed to get DB connection: {}", e))?;
+
+        let status = ProcessorStatusQuery::get_by_processor(&self.processor_name, &mut conn)
+            .await
+            .map_err(|e| format!("Failed to query processor status: {}", e))?;
+
+        Ok(status.map(|s| s.last_updated))
+    }
+}
+
+#[async_trait]
+impl TransactionTimestampProvider for SqliteProgressStatusProvider {
+    async fn get_last_transaction_timestamp(&self) -> Result<Option<NaiveDateTime>, String> {
+        let mut conn = self
+            .db_pool
+            .get()
+            .await
+            .map_err(|e| format!("Failed to get DB connection: {}", e))?;
+
+        let status = ProcessorStatusQuery::get_by_processor(&self.processor_name, &mut conn)
+            .await
+            .map_err(|e| format!("Failed to query processor status: {}", e))?;
+
+        Ok(status.and_then(|s| s.last_transaction_timestamp))
+    }
+}
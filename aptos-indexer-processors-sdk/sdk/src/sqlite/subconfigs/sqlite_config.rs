@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SqliteConfig {
+    /// Path of the database file. `:memory:` only works with a `db_pool_size` of 1, since
+    /// every connection to `:memory:` opens its own database.
+    pub database_path: String,
+    // SQLite allows a single writer at a time, so a large pool only helps concurrent reads.
+    #[serde(default = "SqliteConfig::default_db_pool_size")]
+    pub db_pool_size: u32,
+}
+
+impl SqliteConfig {
+    pub const fn default_db_pool_size() -> u32 {
+        4
+    }
+}
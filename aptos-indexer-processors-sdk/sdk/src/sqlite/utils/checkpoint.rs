@@ -0,0 +1,189 @@
+use super::database::{execute_with_better_error, ArcDbPool};
+use crate::{
+    aptos_indexer_transaction_stream::{utils::time::parse_timestamp, TransactionStreamConfig},
+    common_steps::ProcessorStatusSaver,
+    sqlite::{
+        models::{
+            ledger_info::LedgerInfo,
+            processor_status::{ProcessorStatus, ProcessorStatusQuery},
+        },
+        processor_metadata_schema::{ledger_infos, processor_status},
+    },
+    types::transaction_context::TransactionContext,
+    utils::{chain_id_check::ChainIdChecker, errors::ProcessorError},
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use diesel::{query_dsl::methods::FilterDsl, upsert::excluded, ExpressionMethods};
+
+/// A trait implementation of ChainIdChecker for SQLite.
+pub struct SqliteChainIdChecker {
+    pub db_pool: ArcDbPool,
+}
+
+impl SqliteChainIdChecker {
+    pub fn new(db_pool: ArcDbPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[async_trait]
+impl ChainIdChecker for SqliteChainIdChecker {
+    async fn save_chain_id(&self, chain_id: u64) -> Result<()> {
+        execute_with_better_error(
+            self.db_pool.clone(),
+            diesel::insert_into(ledger_infos::table)
+                .values(LedgerInfo {
+                    chain_id: chain_id as i64,
+                })
+                .on_conflict_do_nothing(),
+        )
+        .await
+        .context("Error updating chain_id!")?;
+        Ok(())
+    }
+
+    async fn get_chain_id(&self) -> Result<Option<u64>> {
+        let mut conn = self.db_pool.get().await?;
+        let maybe_existing_chain_id = LedgerInfo::get(&mut conn)
+            .await?
+            .map(|li| li.chain_id as u64);
+        Ok(maybe_existing_chain_id)
+    }
+}
+
+/// A trait implementation of ProcessorStatusSaver for SQLite.
+pub struct SqliteProcessorStatusSaver {
+    pub db_pool: ArcDbPool,
+    pub processor_name: String,
+}
+
+impl SqliteProcessorStatusSaver {
+    pub fn new(processor_name: &str, db_pool: ArcDbPool) -> Self {
+        Self {
+            db_pool,
+            processor_name: processor_name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ProcessorStatusSaver for SqliteProcessorStatusSaver {
+    async fn save_processor_status(
+        &self,
+        last_success_batch: &TransactionContext<()>,
+    ) -> Result<(), ProcessorError> {
+        let last_success_version = last_success_batch.metadata.end_version as i64;
+        let last_transaction_timestamp = last_success_batch
+            .metadata
+            .end_transaction_timestamp
+            .as_ref()
+            .map(|t| parse_timestamp(t, last_success_batch.metadata.end_version as i64))
+            .map(|t| t.naive_utc());
+        let status = ProcessorStatus {
+            processor: self.processor_name.clone(),
+            last_success_version,
+            last_transaction_timestamp,
+        };
+
+        execute_with_better_error(
+            self.db_pool.clone(),
+            diesel::insert_into(processor_status::table)
+                .values(&status)
+                .on_conflict(processor_status::processor)
+                .do_update()
+                .set((
+                    processor_status::last_success_version
+                        .eq(excluded(processor_status::last_success_version)),
+                    processor_status::last_updated.eq(excluded(processor_status::last_updated)),
+                    processor_status::last_transaction_timestamp
+                        .eq(excluded(processor_status::last_transaction_timestamp)),
+                ))
+                .filter(
+                    processor_status::last_success_version
+                        .le(excluded(processor_status::last_success_version)),
+                ),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+pub async fn get_starting_version(
+    processor_name: &str,
+    transaction_stream_config: TransactionStreamConfig,
+    conn_pool: ArcDbPool,
+) -> Result<u64> {
+    let mut conn = conn_pool.get().await?;
+    let latest_processed_version =
+        ProcessorStatusQuery::get_by_processor(processor_name, &mut conn)
+            .await?
+            .map(|ps| ps.last_success_version as u64);
+    // If nothing checkpointed, return the `starting_version` from the config, or 0 if not set.
+    Ok(latest_processed_version.unwrap_or(transaction_stream_config.starting_version.unwrap_or(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sqlite::{utils::database::new_db_pool, SDK_MIGRATIONS},
+        types::transaction_context::TransactionMetadata,
+    };
+    use diesel::{Connection, SqliteConnection};
+    use diesel_migrations::MigrationHarness;
+
+    fn batch(end_version: u64) -> TransactionContext<()> {
+        TransactionContext {
+            data: (),
+            metadata: TransactionMetadata {
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[allow(clippy::needless_return)]
+    async fn test_checkpoint_and_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let database_path = dir.path().join("processor.db").display().to_string();
+        SqliteConnection::establish(&database_path)
+            .unwrap()
+            .run_pending_migrations(SDK_MIGRATIONS)
+            .unwrap();
+        let db_pool = new_db_pool(&database_path, 1).await.unwrap();
+        let transaction_stream_config = serde_yaml::from_str::<TransactionStreamConfig>(
+            r#"
+            indexer_grpc_data_service_address: "http://localhost:50051"
+            starting_version: 10
+            request_ending_version: null
+            auth_token: "token"
+            request_name_header: "test"
+            "#,
+        )
+        .unwrap();
+
+        // Nothing checkpointed yet, so start from the config.
+        let starting_version =
+            get_starting_version("test", transaction_stream_config.clone(), db_pool.clone())
+                .await
+                .unwrap();
+        assert_eq!(starting_version, 10);
+
+        let saver = SqliteProcessorStatusSaver::new("test", db_pool.clone());
+        saver.save_processor_status(&batch(20)).await.unwrap();
+        // Older checkpoints never move the status backwards.
+        saver.save_processor_status(&batch(15)).await.unwrap();
+        let starting_version =
+            get_starting_version("test", transaction_stream_config, db_pool.clone())
+                .await
+                .unwrap();
+        assert_eq!(starting_version, 20);
+
+        let chain_id_checker = SqliteChainIdChecker::new(db_pool);
+        assert_eq!(chain_id_checker.get_chain_id().await.unwrap(), None);
+        chain_id_checker.save_chain_id(1).await.unwrap();
+        assert_eq!(chain_id_checker.get_chain_id().await.unwrap(), Some(1));
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Database-related functions for SQLite.
+//!
+//! SQLite only has a synchronous driver. `SyncConnectionWrapper` runs every query on the
+//! blocking thread pool, which lets SQLite connections be pooled and used like the
+//! Postgres ones.
+
+use crate::{sqlite::SDK_MIGRATIONS, utils::errors::ProcessorError};
+use diesel::{
+    query_builder::QueryFragment, Connection, ConnectionError, ConnectionResult, SqliteConnection,
+};
+use diesel_async::{
+    pooled_connection::{
+        bb8::{Pool, PooledConnection},
+        AsyncDieselConnectionManager, ManagerConfig, PoolError,
+    },
+    sync_connection_wrapper::SyncConnectionWrapper,
+    AsyncConnection, RunQueryDsl, SimpleAsyncConnection,
+};
+use diesel_migrations::{EmbeddedMigrations, MigrationHarness};
+use futures_util::{future::BoxFuture, FutureExt};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+pub type Backend = diesel::sqlite::Sqlite;
+
+pub type MyDbConnection = SyncConnectionWrapper<SqliteConnection>;
+pub type DbPool = Pool<MyDbConnection>;
+pub type ArcDbPool = Arc<DbPool>;
+pub type DbPoolConnection<'a> = PooledConnection<'a, MyDbConnection>;
+
+/// How long a connection waits for another connection's write lock before failing.
+pub const BUSY_TIMEOUT_MS: u64 = 5_000;
+
+fn establish_connection(database_path: &str) -> BoxFuture<ConnectionResult<MyDbConnection>> {
+    let database_path = database_path.to_string();
+    (async move {
+        let mut conn = MyDbConnection::establish(&database_path).await?;
+        // WAL lets readers proceed while a write is in progress, and the busy timeout makes
+        // concurrent writers queue up instead of failing right away.
+        conn.batch_execute(&format!(
+            "PRAGMA journal_mode = WAL; PRAGMA busy_timeout = {BUSY_TIMEOUT_MS};"
+        ))
+        .await
+        .map_err(|e| ConnectionError::BadConnection(e.to_string()))?;
+        Ok(conn)
+    })
+    .boxed()
+}
+
+pub async fn new_db_pool(database_path: &str, max_pool_size: u32) -> Result<ArcDbPool, PoolError> {
+    let mut config = ManagerConfig::<MyDbConnection>::default();
+    config.custom_setup = Box::new(establish_connection);
+    let manager =
+        AsyncDieselConnectionManager::<MyDbConnection>::new_with_config(database_path, config);
+    let pool = Pool::builder()
+        .max_size(max_pool_size)
+        .build(manager)
+        .await?;
+    Ok(Arc::new(pool))
+}
+
+pub async fn execute_with_better_error<U>(
+    pool: ArcDbPool,
+    query: U,
+) -> Result<usize, ProcessorError>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send,
+{
+    let debug_string = diesel::debug_query::<Backend, _>(&query).to_string();
+    let conn = &mut pool.get().await.map_err(|e| {
+        warn!("Error getting connection from pool: {:?}", e);
+        ProcessorError::DBStoreError {
+            message: format!("{e:#}"),
+            query: Some(debug_string.clone()),
+        }
+    })?;
+    query
+        .execute(conn)
+        .await
+        .inspect_err(|e| {
+            warn!("Error running query: {:?}\n{:?}", e, debug_string);
+        })
+        .map_err(|e| ProcessorError::DBStoreError {
+            message: format!("{e:#}"),
+            query: Some(debug_string),
+        })
+}
+
+/// Runs the user's migrations followed by the SDK migrations (`SDK_MIGRATIONS`).
+pub async fn run_user_and_sdk_migrations(database_path: String, migrations: EmbeddedMigrations) {
+    info!("Running migrations: {:?}", database_path);
+    let migration_time = std::time::Instant::now();
+    // We use spawn_blocking since run_pending_migrations is a blocking function.
+    tokio::task::spawn_blocking(move || {
+        let mut conn =
+            SqliteConnection::establish(&database_path).expect("[Parser] Migrations failed!");
+        for migrations in [migrations, SDK_MIGRATIONS] {
+            conn.run_pending_migrations(migrations)
+                .expect("[Parser] Migrations failed!");
+        }
+    })
+    .await
+    .expect("[Parser] Failed to run migrations");
+    info!(
+        duration_in_secs = migration_time.elapsed().as_secs_f64(),
+        "[Parser] Finished migrations"
+    );
+}
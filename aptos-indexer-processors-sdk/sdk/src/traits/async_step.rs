@@ -12,7 +12,7 @@ use instrumented_channel::{
 };
 use std::time::{Duration, Instant};
 use tokio::task::JoinHandle;
-use tracing::{error, info, warn};
+use tracing::{error, info, warn, Instrument};
 
 #[async_trait]
 pub trait AsyncStep
@@ -100,17 +100,19 @@ where
                     },
                 };
                 let processing_duration = Instant::now();
-                let output_with_context = match step.process(input_with_context).await {
-                    Ok(output_with_context) => output_with_context,
-                    Err(e) => {
-                        error!(
-                            step_name = step_name,
-                            error = e.to_string(),
-                            "Failed to process input"
-                        );
-                        break;
-                    },
-                };
+                let span = input_with_context.metadata.step_span(&step_name);
+                let output_with_context =
+                    match step.process(input_with_context).instrument(span).await {
+                        Ok(output_with_context) => output_with_context,
+                        Err(e) => {
+                            error!(
+                                step_name = step_name,
+                                error = e.to_string(),
+                                "Failed to process input"
+                            );
+                            break;
+                        },
+                    };
                 if let Some(output_with_context) = output_with_context {
                     match StepMetricsBuilder::default()
                         .labels(StepMetricLabels {
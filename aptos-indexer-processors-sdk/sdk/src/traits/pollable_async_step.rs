@@ -19,7 +19,7 @@ use std::{
     time::{Duration, Instant},
 };
 use tokio::{sync::Mutex, task::JoinHandle};
-use tracing::{error, info, warn};
+use tracing::{error, info, warn, Instrument};
 
 #[async_trait]
 pub trait PollableAsyncStep
@@ -218,18 +218,24 @@ where
                         },
                     };
                     let processing_duration = Instant::now();
-                    let output_with_context =
-                        match process_step.lock().await.process(input_with_context).await {
-                            Ok(output_with_context) => output_with_context,
-                            Err(e) => {
-                                error!(
-                                    step_name = process_step_name,
-                                    error = e.to_string(),
-                                    "Failed to process input"
-                                );
-                                break;
-                            },
-                        };
+                    let span = input_with_context.metadata.step_span(&process_step_name);
+                    let output_with_context = match process_step
+                        .lock()
+                        .await
+                        .process(input_with_context)
+                        .instrument(span)
+                        .await
+                    {
+                        Ok(output_with_context) => output_with_context,
+                        Err(e) => {
+                            error!(
+                                step_name = process_step_name,
+                                error = e.to_string(),
+                                "Failed to process input"
+                            );
+                            break;
+                        },
+                    };
                     if let Some(output_with_context) = output_with_context {
                         match StepMetricsBuilder::default()
                             .labels(StepMetricLabels {
@@ -1,6 +1,7 @@
 use aptos_indexer_transaction_stream::utils::time::{
     time_diff_since_pb_timestamp_in_secs, timestamp_to_unixtime,
 };
+use tracing::{info_span, Span};
 
 /// Contains processed data and associated transaction metadata.
 ///
@@ -63,4 +64,29 @@ pub struct TransactionMetadata {
     pub start_transaction_timestamp: Option<aptos_protos::util::timestamp::Timestamp>,
     pub end_transaction_timestamp: Option<aptos_protos::util::timestamp::Timestamp>,
     pub total_size_in_bytes: u64,
+    /// Span of the batch's way through the pipeline, see `batch_span`. Steps keep it when they
+    /// pass the metadata on, and every step's `process` call is traced as its child, so
+    /// exporting the spans, e.g. with `otel::OtlpConfig`, shows where a batch spent its time.
+    pub span: Span,
+}
+
+impl TransactionMetadata {
+    /// Extends this metadata to also cover `next`, the metadata of the batch that directly
+    /// follows it.
+    pub fn extend(&mut self, next: &TransactionMetadata) {
+        self.end_version = next.end_version;
+        self.end_transaction_timestamp = next.end_transaction_timestamp.clone();
+        self.total_size_in_bytes += next.total_size_in_bytes;
+        self.span.follows_from(&next.span);
+    }
+
+    /// Creates the span of a new batch of transactions, for source steps to set as `span`.
+    pub fn batch_span(start_version: u64, end_version: u64) -> Span {
+        info_span!(parent: None, "batch", start_version, end_version)
+    }
+
+    /// Creates the span of `step_name` processing this batch, as a child of the batch's span.
+    pub fn step_span(&self, step_name: &str) -> Span {
+        info_span!(parent: &self.span, "process", otel.name = step_name, step_name)
+    }
 }
@@ -0,0 +1,271 @@
+//! File-backed checkpointing for processors that don't write to a database, e.g. processors
+//! writing to object storage, files or message queues.
+//!
+//! The checkpoint is a small JSON file holding the chain id and the last successfully
+//! processed version. Every write goes to a temporary file that is fsynced and then renamed
+//! over the checkpoint, so a crash leaves either the old or the new checkpoint, never a
+//! partial one.
+
+use super::{chain_id_check::ChainIdChecker, errors::ProcessorError};
+use crate::{
+    aptos_indexer_transaction_stream::{utils::time::parse_timestamp, TransactionStreamConfig},
+    common_steps::ProcessorStatusSaver,
+    types::transaction_context::TransactionContext,
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+
+/// Contents of the checkpoint file.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct FileCheckpoint {
+    pub processor: String,
+    pub chain_id: Option<u64>,
+    pub last_success_version: Option<u64>,
+    pub last_transaction_timestamp: Option<NaiveDateTime>,
+    pub last_updated: Option<NaiveDateTime>,
+}
+
+/// Stores a processor's checkpoint in a JSON file. Implements both `ProcessorStatusSaver`
+/// and `ChainIdChecker`. Clones share the same lock, so they can be used as the saver and
+/// the checker at the same time.
+#[derive(Clone)]
+pub struct FileCheckpointStore {
+    processor_name: String,
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl FileCheckpointStore {
+    pub fn new(processor_name: &str, path: impl Into<PathBuf>) -> Self {
+        Self {
+            processor_name: processor_name.to_string(),
+            path: path.into(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads the checkpoint, or returns `None` if it hasn't been written yet. Fails if the
+    /// checkpoint belongs to another processor.
+    pub async fn read(&self) -> Result<Option<FileCheckpoint>> {
+        let _guard = self.lock.lock().await;
+        let path = self.path.clone();
+        let processor_name = self.processor_name.clone();
+        tokio::task::spawn_blocking(move || read_checkpoint(&path, &processor_name))
+            .await
+            .context("Task panicked reading checkpoint")?
+    }
+
+    /// Applies `update` to the current checkpoint and atomically writes the result.
+    async fn update<F>(&self, update: F) -> Result<()>
+    where
+        F: FnOnce(&mut FileCheckpoint) + Send + 'static,
+    {
+        let _guard = self.lock.lock().await;
+        let path = self.path.clone();
+        let processor_name = self.processor_name.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut checkpoint =
+                read_checkpoint(&path, &processor_name)?.unwrap_or_else(|| FileCheckpoint {
+                    processor: processor_name,
+                    ..FileCheckpoint::default()
+                });
+            update(&mut checkpoint);
+            write_checkpoint_atomically(&path, &checkpoint)
+        })
+        .await
+        .context("Task panicked writing checkpoint")?
+    }
+}
+
+fn read_checkpoint(path: &Path, processor_name: &str) -> Result<Option<FileCheckpoint>> {
+    let checkpoint: FileCheckpoint = match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .with_context(|| format!("Invalid checkpoint file at {path:?}"))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read checkpoint file at {path:?}"))
+        },
+    };
+    // E.g. a file left over from another processor, or a copied path in the config.
+    if checkpoint.processor != processor_name {
+        bail!(
+            "Checkpoint file at {path:?} belongs to processor {}, not {}",
+            checkpoint.processor,
+            processor_name
+        );
+    }
+    Ok(Some(checkpoint))
+}
+
+fn write_checkpoint_atomically(path: &Path, checkpoint: &FileCheckpoint) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let mut file = File::create(&tmp_path)
+        .with_context(|| format!("Failed to create checkpoint file at {tmp_path:?}"))?;
+    file.write_all(&serde_json::to_vec_pretty(checkpoint)?)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to move checkpoint file to {path:?}"))?;
+    // The rename is only durable once the directory entry is synced.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl ProcessorStatusSaver for FileCheckpointStore {
+    async fn save_processor_status(
+        &self,
+        last_success_batch: &TransactionContext<()>,
+    ) -> Result<(), ProcessorError> {
+        let end_version = last_success_batch.metadata.end_version;
+        let last_transaction_timestamp = last_success_batch
+            .metadata
+            .end_transaction_timestamp
+            .as_ref()
+            .map(|t| parse_timestamp(t, end_version as i64))
+            .map(|t| t.naive_utc());
+        self.update(move |checkpoint| {
+            // Like the Postgres saver, never move the checkpoint backwards.
+            if checkpoint
+                .last_success_version
+                .is_some_and(|version| version > end_version)
+            {
+                return;
+            }
+            checkpoint.last_success_version = Some(end_version);
+            checkpoint.last_transaction_timestamp = last_transaction_timestamp;
+            checkpoint.last_updated = Some(chrono::Utc::now().naive_utc());
+        })
+        .await
+        .map_err(|e| ProcessorError::ProcessError {
+            message: format!("Error saving checkpoint: {e:?}"),
+        })
+    }
+}
+
+#[async_trait]
+impl ChainIdChecker for FileCheckpointStore {
+    async fn save_chain_id(&self, chain_id: u64) -> Result<()> {
+        self.update(move |checkpoint| {
+            checkpoint.chain_id.get_or_insert(chain_id);
+        })
+        .await
+    }
+
+    async fn get_chain_id(&self) -> Result<Option<u64>> {
+        Ok(self
+            .read()
+            .await?
+            .and_then(|checkpoint| checkpoint.chain_id))
+    }
+}
+
+#[cfg(feature = "server_framework")]
+#[async_trait]
+impl crate::health::ProgressStatusProvider for FileCheckpointStore {
+    async fn get_last_updated(&self) -> Result<Option<NaiveDateTime>, String> {
+        self.read()
+            .await
+            .map(|checkpoint| checkpoint.and_then(|checkpoint| checkpoint.last_updated))
+            .map_err(|e| format!("Failed to read checkpoint: {e:?}"))
+    }
+}
+
+#[cfg(feature = "server_framework")]
+#[async_trait]
+impl crate::health::TransactionTimestampProvider for FileCheckpointStore {
+    async fn get_last_transaction_timestamp(&self) -> Result<Option<NaiveDateTime>, String> {
+        self.read()
+            .await
+            .map(|checkpoint| {
+                checkpoint.and_then(|checkpoint| checkpoint.last_transaction_timestamp)
+            })
+            .map_err(|e| format!("Failed to read checkpoint: {e:?}"))
+    }
+}
+
+pub async fn get_starting_version(
+    checkpoint_store: &FileCheckpointStore,
+    transaction_stream_config: &TransactionStreamConfig,
+) -> Result<u64> {
+    let latest_processed_version = checkpoint_store
+        .read()
+        .await?
+        .and_then(|checkpoint| checkpoint.last_success_version);
+    // If nothing checkpointed, return the `starting_version` from the config, or 0 if not set.
+    Ok(latest_processed_version.unwrap_or(transaction_stream_config.starting_version.unwrap_or(0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction_context::TransactionMetadata;
+
+    fn batch(end_version: u64) -> TransactionContext<()> {
+        TransactionContext {
+            data: (),
+            metadata: TransactionMetadata {
+                end_version,
+                ..TransactionMetadata::default()
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_file_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileCheckpointStore::new("test", dir.path().join("checkpoint.json"));
+        assert_eq!(store.read().await.unwrap(), None);
+        assert_eq!(store.get_chain_id().await.unwrap(), None);
+
+        store.save_chain_id(1).await.unwrap();
+        store.save_processor_status(&batch(20)).await.unwrap();
+        // Older checkpoints never move the status backwards.
+        store.save_processor_status(&batch(15)).await.unwrap();
+
+        // A new store on the same file resumes from the checkpoint.
+        let store = FileCheckpointStore::new("test", dir.path().join("checkpoint.json"));
+        let checkpoint = store.read().await.unwrap().unwrap();
+        assert_eq!(checkpoint.processor, "test");
+        assert_eq!(checkpoint.chain_id, Some(1));
+        assert_eq!(checkpoint.last_success_version, Some(20));
+        assert!(checkpoint.last_updated.is_some());
+        assert!(!dir.path().join("checkpoint.json.tmp").exists());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_file_checkpoint_of_other_processor() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.json");
+        let store = FileCheckpointStore::new("test", &path);
+        store.save_processor_status(&batch(20)).await.unwrap();
+
+        let other_store = FileCheckpointStore::new("other", &path);
+        let error = other_store.read().await.unwrap_err();
+        assert!(error.to_string().contains("belongs to processor test"));
+        // The other processor doesn't overwrite the checkpoint either.
+        assert!(other_store.save_processor_status(&batch(30)).await.is_err());
+        let checkpoint = store.read().await.unwrap().unwrap();
+        assert_eq!(checkpoint.last_success_version, Some(20));
+    }
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, NaiveDateTime};
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc,
+};
+
+const UNSET: i64 = i64::MIN;
+
+/// The newest transaction timestamp seen so far, e.g. by `TransactionStreamStep`. Clones share
+/// the same value, so one clone can be recorded into while another is read, for example by
+/// `ChainLagHealthChecker`.
+#[derive(Clone, Debug)]
+pub struct LatestSeenTimestamp {
+    micros: Arc<AtomicI64>,
+}
+
+impl Default for LatestSeenTimestamp {
+    fn default() -> Self {
+        Self {
+            micros: Arc::new(AtomicI64::new(UNSET)),
+        }
+    }
+}
+
+impl LatestSeenTimestamp {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `timestamp` if it's newer than the one seen so far.
+    pub fn record(&self, timestamp: NaiveDateTime) {
+        self.micros
+            .fetch_max(timestamp.and_utc().timestamp_micros(), Ordering::Relaxed);
+    }
+
+    /// Returns `None` until a timestamp has been recorded.
+    pub fn get(&self) -> Option<NaiveDateTime> {
+        match self.micros.load(Ordering::Relaxed) {
+            UNSET => None,
+            micros => DateTime::from_timestamp_micros(micros).map(|t| t.naive_utc()),
+        }
+    }
+}
@@ -3,5 +3,8 @@ pub mod constants;
 pub mod convert;
 pub mod errors;
 pub mod extract;
+pub mod file_checkpoint;
+pub mod latest_seen_timestamp;
+pub mod pipeline_status;
 pub mod property_map;
 pub mod step_metrics;
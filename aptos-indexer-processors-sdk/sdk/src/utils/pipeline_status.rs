@@ -0,0 +1,183 @@
+//! Process-wide state of the running pipeline, like the metrics registries: whether it's
+//! paused, the latest version each step has seen and the transaction streams it reads from.
+//! This is what the admin endpoints read and control.
+
+use crate::aptos_indexer_transaction_stream::StreamConnectionStats;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::sync::watch;
+
+/// Pauses and resumes the source steps of the pipeline. `TransactionStreamStep` waits while
+/// paused before fetching the next batch, so the rest of the pipeline drains and then idles.
+///
+/// The built-in liveness checks pass while paused, so that a paused pod isn't restarted.
+pub struct PauseController {
+    paused: watch::Sender<bool>,
+    resumed_at: Mutex<Option<Instant>>,
+}
+
+impl PauseController {
+    pub(crate) fn new() -> Self {
+        Self {
+            paused: watch::Sender::new(false),
+            resumed_at: Mutex::new(None),
+        }
+    }
+
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    pub fn resume(&self) {
+        if self.paused.send_replace(false) {
+            *self.resumed_at.lock().unwrap() = Some(Instant::now());
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Whether the pipeline is paused or was resumed less than `window` ago. Health checks
+    /// use this to give the pipeline time to make progress again after a resume.
+    pub fn paused_within(&self, window: Duration) -> bool {
+        self.is_paused()
+            || self
+                .resumed_at
+                .lock()
+                .unwrap()
+                .is_some_and(|resumed_at| resumed_at.elapsed() < window)
+    }
+
+    /// Returns once the pipeline isn't paused.
+    pub async fn wait_while_paused(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender lives in a static, so it's never dropped.
+        let _ = paused.wait_for(|paused| !paused).await;
+    }
+}
+
+static PAUSE_CONTROLLER: Lazy<PauseController> = Lazy::new(PauseController::new);
+
+pub fn pause_controller() -> &'static PauseController {
+    &PAUSE_CONTROLLER
+}
+
+/// Latest versions logged by a step.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct StepVersions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_processed_version: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest_polled_version: Option<u64>,
+}
+
+static STEP_VERSIONS: Lazy<Mutex<BTreeMap<String, StepVersions>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+pub(crate) fn record_step_versions(
+    step_name: &str,
+    latest_processed_version: Option<u64>,
+    latest_polled_version: Option<u64>,
+) {
+    let mut step_versions = STEP_VERSIONS.lock().unwrap();
+    let versions = match step_versions.get_mut(step_name) {
+        Some(versions) => versions,
+        None => step_versions.entry(step_name.to_string()).or_default(),
+    };
+    if latest_processed_version.is_some() {
+        versions.latest_processed_version = latest_processed_version;
+    }
+    if latest_polled_version.is_some() {
+        versions.latest_polled_version = latest_polled_version;
+    }
+}
+
+/// Latest versions of every step, by step name.
+pub fn step_versions() -> BTreeMap<String, StepVersions> {
+    STEP_VERSIONS.lock().unwrap().clone()
+}
+
+/// Connection of a transaction stream the pipeline reads from.
+#[derive(Clone, Debug, Serialize)]
+pub struct StreamConnectionStatus {
+    pub stream_address: Option<String>,
+    pub connection_id: Option<String>,
+}
+
+static NEXT_STREAM_ID: AtomicU64 = AtomicU64::new(0);
+static STREAMS: Lazy<Mutex<BTreeMap<u64, StreamConnectionStats>>> =
+    Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+/// Keeps a stream listed in `stream_connections` until dropped.
+pub(crate) struct StreamRegistration {
+    id: u64,
+}
+
+impl StreamRegistration {
+    pub(crate) fn new(connection_stats: StreamConnectionStats) -> Self {
+        let id = NEXT_STREAM_ID.fetch_add(1, Ordering::Relaxed);
+        STREAMS.lock().unwrap().insert(id, connection_stats);
+        Self { id }
+    }
+
+    pub(crate) fn update(&self, connection_stats: StreamConnectionStats) {
+        STREAMS.lock().unwrap().insert(self.id, connection_stats);
+    }
+}
+
+impl Drop for StreamRegistration {
+    fn drop(&mut self) {
+        STREAMS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Connections of the transaction streams that are currently open.
+pub fn stream_connections() -> Vec<StreamConnectionStatus> {
+    STREAMS
+        .lock()
+        .unwrap()
+        .values()
+        .map(|connection_stats| StreamConnectionStatus {
+            stream_address: connection_stats.stream_address(),
+            connection_id: connection_stats.connection_id(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_pause_controller() {
+        // A controller of its own, so that other tests don't see the pause.
+        let controller: &'static PauseController = Box::leak(Box::new(PauseController::new()));
+        controller.pause();
+        assert!(controller.is_paused());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), controller.wait_while_paused())
+                .await
+                .is_err()
+        );
+
+        let waiter = tokio::spawn(async move { controller.wait_while_paused().await });
+        controller.resume();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!controller.is_paused());
+        assert!(controller.paused_within(Duration::from_secs(60)));
+        assert!(!controller.paused_within(Duration::ZERO));
+    }
+}
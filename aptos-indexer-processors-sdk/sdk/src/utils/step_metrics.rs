@@ -1,3 +1,4 @@
+use super::pipeline_status::record_step_versions;
 use derive_builder::Builder;
 use once_cell::sync::Lazy;
 use prometheus_client::{
@@ -114,6 +115,47 @@ pub fn init_step_metrics_registry(registry: &mut Registry) {
         "WriteRateLimitStep bytes written",
         WRITE_RATE_LIMIT_STEP_BYTES_WRITTEN.clone(),
     );
+
+    registry.register(
+        format!(
+            "{}_{}",
+            METRICS_PREFIX, "write_rate_limit_effective_bytes_per_sec"
+        ),
+        "WriteRateLimitStep current fill rate, which changes over time in the adaptive mode",
+        WRITE_RATE_LIMIT_STEP_EFFECTIVE_BYTES_PER_SEC.clone(),
+    );
+
+    // OrderByVersionStep metrics
+    registry.register(
+        format!("{}_{}", METRICS_PREFIX, "order_by_version_buffered_batches"),
+        "OrderByVersionStep number of out of order batches waiting for a gap to close",
+        ORDER_BY_VERSION_STEP_BUFFERED_BATCHES.clone(),
+    );
+
+    registry.register(
+        format!("{}_{}", METRICS_PREFIX, "order_by_version_buffered_bytes"),
+        "OrderByVersionStep size of the out of order batches waiting for a gap to close",
+        ORDER_BY_VERSION_STEP_BUFFERED_BYTES.clone(),
+    );
+
+    registry.register(
+        format!("{}_{}", METRICS_PREFIX, "order_by_version_gap_age_secs"),
+        "OrderByVersionStep seconds since the oldest open gap was detected, 0 if none",
+        ORDER_BY_VERSION_STEP_GAP_AGE_SECS.clone(),
+    );
+
+    // VersionTrackerStep metrics
+    registry.register(
+        format!("{}_{}", METRICS_PREFIX, "version_tracker_gap_count"),
+        "VersionTrackerStep number of missing version ranges past the checkpoint",
+        VERSION_TRACKER_STEP_GAP_COUNT.clone(),
+    );
+
+    registry.register(
+        format!("{}_{}", METRICS_PREFIX, "version_tracker_gap_age_secs"),
+        "VersionTrackerStep seconds the checkpoint has been blocked by a gap, 0 if none",
+        VERSION_TRACKER_STEP_GAP_AGE_SECS.clone(),
+    );
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
@@ -174,6 +216,29 @@ pub static WRITE_RATE_LIMIT_STEP_REMAINING_BYTES: Lazy<Family<StepMetricLabels,
 pub static WRITE_RATE_LIMIT_STEP_BYTES_WRITTEN: Lazy<Family<StepMetricLabels, Counter>> =
     Lazy::new(Family::<StepMetricLabels, Counter>::default);
 
+pub static WRITE_RATE_LIMIT_STEP_EFFECTIVE_BYTES_PER_SEC: Lazy<
+    Family<StepMetricLabels, Gauge<f64, AtomicU64>>,
+> = Lazy::new(Family::<StepMetricLabels, Gauge<f64, AtomicU64>>::default);
+
+// OrderByVersionStep metrics
+pub static ORDER_BY_VERSION_STEP_BUFFERED_BATCHES: Lazy<Family<StepMetricLabels, Gauge>> =
+    Lazy::new(Family::<StepMetricLabels, Gauge>::default);
+
+pub static ORDER_BY_VERSION_STEP_BUFFERED_BYTES: Lazy<Family<StepMetricLabels, Gauge>> =
+    Lazy::new(Family::<StepMetricLabels, Gauge>::default);
+
+pub static ORDER_BY_VERSION_STEP_GAP_AGE_SECS: Lazy<
+    Family<StepMetricLabels, Gauge<f64, AtomicU64>>,
+> = Lazy::new(Family::<StepMetricLabels, Gauge<f64, AtomicU64>>::default);
+
+// VersionTrackerStep metrics
+pub static VERSION_TRACKER_STEP_GAP_COUNT: Lazy<Family<StepMetricLabels, Gauge>> =
+    Lazy::new(Family::<StepMetricLabels, Gauge>::default);
+
+pub static VERSION_TRACKER_STEP_GAP_AGE_SECS: Lazy<
+    Family<StepMetricLabels, Gauge<f64, AtomicU64>>,
+> = Lazy::new(Family::<StepMetricLabels, Gauge<f64, AtomicU64>>::default);
+
 #[derive(Builder)]
 pub struct StepMetrics {
     pub labels: StepMetricLabels,
@@ -208,6 +273,14 @@ pub struct StepMetrics {
 
 impl StepMetrics {
     pub fn log_metrics(&mut self) {
+        if self.latest_processed_version.is_some() || self.latest_polled_version.is_some() {
+            record_step_versions(
+                &self.labels.step_name,
+                self.latest_processed_version,
+                self.latest_polled_version,
+            );
+        }
+
         // AsyncStep metrics
         if let Some(version) = self.latest_processed_version {
             LATEST_PROCESSED_VERSION
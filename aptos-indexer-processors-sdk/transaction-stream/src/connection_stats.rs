@@ -0,0 +1,123 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// How many reconnects are remembered. Older ones are dropped.
+const MAX_TRACKED_RECONNECTS: usize = 1000;
+
+#[derive(Default)]
+struct ConnectionStatsInner {
+    stream_address: Option<String>,
+    connection_id: Option<String>,
+    connected_at: Option<Instant>,
+    last_received_at: Option<Instant>,
+    reconnects: VecDeque<Instant>,
+}
+
+/// Connection state of a `TransactionStream`, shared with health checks and admin endpoints.
+/// Clones share the same state.
+#[derive(Clone, Default)]
+pub struct StreamConnectionStats {
+    inner: Arc<Mutex<ConnectionStatsInner>>,
+}
+
+impl StreamConnectionStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record_connected(&self, stream_address: &str, connection_id: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.stream_address = Some(stream_address.to_string());
+        inner.connection_id = Some(connection_id.to_string());
+        inner.connected_at = Some(Instant::now());
+    }
+
+    pub(crate) fn record_reconnected(&self, connection_id: &str) {
+        let now = Instant::now();
+        let mut inner = self.inner.lock().unwrap();
+        inner.connection_id = Some(connection_id.to_string());
+        inner.connected_at = Some(now);
+        inner.reconnects.push_back(now);
+        if inner.reconnects.len() > MAX_TRACKED_RECONNECTS {
+            inner.reconnects.pop_front();
+        }
+    }
+
+    pub(crate) fn copy_from(&self, other: &StreamConnectionStats) {
+        if Arc::ptr_eq(&self.inner, &other.inner) {
+            return;
+        }
+        let other = other.inner.lock().unwrap();
+        let mut inner = self.inner.lock().unwrap();
+        inner.stream_address.clone_from(&other.stream_address);
+        inner.connection_id.clone_from(&other.connection_id);
+        inner.connected_at = other.connected_at;
+        inner.last_received_at = other.last_received_at;
+        inner.reconnects.clone_from(&other.reconnects);
+    }
+
+    pub(crate) fn record_received(&self) {
+        self.inner.lock().unwrap().last_received_at = Some(Instant::now());
+    }
+
+    /// Address of the data service the stream is connected to, or `None` before it connects.
+    pub fn stream_address(&self) -> Option<String> {
+        self.inner.lock().unwrap().stream_address.clone()
+    }
+
+    /// Id the data service assigned to the current connection.
+    pub fn connection_id(&self) -> Option<String> {
+        self.inner.lock().unwrap().connection_id.clone()
+    }
+
+    /// When the current connection was established.
+    pub fn connected_at(&self) -> Option<Instant> {
+        self.inner.lock().unwrap().connected_at
+    }
+
+    /// When a batch of transactions was last received successfully.
+    pub fn last_received_at(&self) -> Option<Instant> {
+        self.inner.lock().unwrap().last_received_at
+    }
+
+    /// Number of successful reconnects within the last `window`.
+    pub fn reconnects_within(&self, window: Duration) -> usize {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .reconnects
+            .iter()
+            .rev()
+            .take_while(|reconnected_at| reconnected_at.elapsed() <= window)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_connection_stats() {
+        let stats = StreamConnectionStats::new();
+        assert_eq!(stats.connection_id(), None);
+
+        stats.record_connected("http://localhost:50051/", "a");
+        stats.record_reconnected("b");
+        stats.record_reconnected("c");
+        assert_eq!(stats.connection_id().as_deref(), Some("c"));
+        assert_eq!(stats.reconnects_within(Duration::from_secs(60)), 2);
+
+        // A new handle picks up the state of the old one.
+        let new_stats = StreamConnectionStats::new();
+        new_stats.copy_from(&stats);
+        new_stats.copy_from(&new_stats.clone());
+        assert_eq!(
+            new_stats.stream_address().as_deref(),
+            Some("http://localhost:50051/")
+        );
+        assert_eq!(new_stats.reconnects_within(Duration::from_secs(60)), 2);
+    }
+}
@@ -1,7 +1,9 @@
 pub mod config;
+pub mod connection_stats;
 pub mod transaction_stream;
 pub mod utils;
 
 pub use aptos_transaction_filter::*;
 pub use config::TransactionStreamConfig;
+pub use connection_stats::StreamConnectionStats;
 pub use transaction_stream::{TransactionStream, TransactionsPBResponse};
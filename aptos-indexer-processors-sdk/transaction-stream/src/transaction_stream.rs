@@ -1,5 +1,6 @@
 use crate::{
     config::TransactionStreamConfig,
+    connection_stats::StreamConnectionStats,
     utils::{additional_headers::AdditionalHeaders, time::timestamp_to_iso},
 };
 use anyhow::{anyhow, Result};
@@ -12,7 +13,7 @@ use aptos_protos::{
 use aptos_transaction_filter::BooleanTransactionFilter;
 use futures_util::StreamExt;
 use prost::Message;
-use sample::{sample, SampleRate};
+use sample::{sample_with_policy, SampleRate};
 use std::time::Duration;
 use tokio::time::timeout;
 use tonic::{
@@ -30,6 +31,8 @@ const GRPC_REQUEST_NAME_HEADER: &str = "x-aptos-request-name";
 const GRPC_CONNECTION_ID: &str = "x-aptos-connection-id";
 /// 256MB
 pub const MAX_RESPONSE_SIZE: usize = 1024 * 1024 * 256;
+/// Sampling key of the log of every received batch, logged at most once a second by default.
+pub const RECEIVED_TRANSACTIONS_SAMPLE_KEY: &str = "transaction_stream.received_transactions";
 
 /// TransactionsPBResponse is a struct that holds the transactions fetched from the stream.
 /// It also includes some contextual information about the transactions.
@@ -382,11 +385,19 @@ pub struct TransactionStream {
     reconnection_retries: u64,
     last_fetched_version: Option<i64>,
     fetch_ma: MovingAverage,
+    connection_stats: StreamConnectionStats,
 }
 
 impl TransactionStream {
     pub async fn new(transaction_stream_config: TransactionStreamConfig) -> Result<Self> {
         let (stream, connection_id) = Self::init_stream(transaction_stream_config.clone()).await?;
+        let connection_stats = StreamConnectionStats::new();
+        connection_stats.record_connected(
+            transaction_stream_config
+                .indexer_grpc_data_service_address
+                .as_str(),
+            &connection_id,
+        );
         Ok(Self {
             transaction_stream_config: transaction_stream_config.clone(),
             stream,
@@ -396,6 +407,7 @@ impl TransactionStream {
                 .starting_version
                 .map(|v| v as i64 - 1),
             fetch_ma: MovingAverage::new(3000),
+            connection_stats,
         })
     }
 
@@ -452,6 +464,7 @@ impl TransactionStream {
                 match response {
                     Some(Ok(r)) => {
                         self.reconnection_retries = 0;
+                        self.connection_stats.record_received();
 
                         // The processed range may not exist if using the v1 transaction stream.
                         // In the case that it doesn't exist, use the previous behavior of using the transaction version of the first and last transactions.
@@ -478,7 +491,8 @@ impl TransactionStream {
                         let duration_in_secs = grpc_channel_recv_latency.elapsed().as_secs_f64();
                         self.fetch_ma.tick_now(num_txns as u64);
 
-                        sample!(
+                        sample_with_policy!(
+                            RECEIVED_TRANSACTIONS_SAMPLE_KEY,
                             SampleRate::Duration(Duration::from_secs(1)),
                             info!(
                                 stream_address = self
@@ -575,6 +589,18 @@ impl TransactionStream {
         txn_pb_res
     }
 
+    /// Returns a handle to the stream's connection state.
+    pub fn connection_stats(&self) -> StreamConnectionStats {
+        self.connection_stats.clone()
+    }
+
+    /// Records the stream's connection state in `connection_stats` from now on, e.g. a handle
+    /// that health checks were created with before the stream was.
+    pub fn set_connection_stats(&mut self, connection_stats: StreamConnectionStats) {
+        connection_stats.copy_from(&self.connection_stats);
+        self.connection_stats = connection_stats;
+    }
+
     /// Helper function to signal that we've fetched all the transactions up to the ending version that was requested.
     pub fn is_end_of_stream(&self) -> bool {
         if let (Some(ending_version), Some(last_fetched_version)) = (
@@ -653,6 +679,7 @@ impl TransactionStream {
             Some(connection_id) => connection_id.to_str().unwrap().to_string(),
             None => "".to_string(),
         };
+        self.connection_stats.record_reconnected(&connection_id);
         self.connection_id = connection_id;
         self.stream = response.into_inner();
         info!(